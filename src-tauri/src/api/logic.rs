@@ -0,0 +1,27 @@
+use crate::error::AppError;
+
+use super::{ApiServer, DEFAULT_PORT};
+
+#[tauri::command]
+pub async fn start_api_server(
+    app_handle: tauri::AppHandle,
+    port: Option<u16>,
+    state: tauri::State<'_, ApiServer>,
+) -> Result<String, AppError> {
+    let port = port.unwrap_or(DEFAULT_PORT);
+    tracing::info!("Starting the API server on port {port}");
+    state.start(app_handle, port).await?;
+    Ok(format!("API server listening on http://127.0.0.1:{port}/v1"))
+}
+
+#[tauri::command]
+pub async fn stop_api_server(state: tauri::State<'_, ApiServer>) -> Result<String, AppError> {
+    tracing::info!("Stopping the API server");
+    state.stop().await?;
+    Ok(String::from("API server stopped"))
+}
+
+#[tauri::command]
+pub async fn api_server_status(state: tauri::State<'_, ApiServer>) -> Result<Option<u16>, AppError> {
+    Ok(state.port().await)
+}
@@ -0,0 +1,78 @@
+use std::{net::SocketAddr, sync::Arc};
+use tauri::async_runtime::Mutex;
+use tauri::{App, AppHandle, Manager};
+use tokio::sync::oneshot;
+
+use crate::error::AppError;
+
+pub mod logic;
+mod routes;
+
+/// Port used when the user did not pick one
+pub const DEFAULT_PORT: u16 = 8080;
+
+struct RunningServer {
+    port: u16,
+    shutdown: oneshot::Sender<()>,
+}
+
+/// Embedded OpenAI compatible HTTP server, stopped by default
+#[derive(Default)]
+pub struct ApiServer {
+    running: Arc<Mutex<Option<RunningServer>>>,
+}
+
+impl ApiServer {
+    pub fn init(app: &App) -> Result<(), AppError> {
+        app.manage(ApiServer::default());
+        Ok(())
+    }
+
+    pub async fn start(&self, app_handle: AppHandle, port: u16) -> Result<(), AppError> {
+        let mut running = self.running.lock().await;
+        if let Some(server) = running.as_ref() {
+            return Err(AppError::ApiServer {
+                reason: format!("already running on port {}", server.port),
+            });
+        }
+        // Only reachable from this computer, the API has no authentication
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let server = axum::Server::try_bind(&addr)
+            .map_err(|err| AppError::ApiServer {
+                reason: err.to_string(),
+            })?
+            .serve(routes::router(app_handle).into_make_service());
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        tauri::async_runtime::spawn(async move {
+            let server = server.with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+            match server.await {
+                Ok(()) => tracing::info!("API server stopped"),
+                Err(err) => tracing::error!("API server error: {err}"),
+            }
+        });
+        tracing::info!("API server listening on http://{addr}");
+        *running = Some(RunningServer { port, shutdown });
+        Ok(())
+    }
+
+    pub async fn stop(&self) -> Result<(), AppError> {
+        match self.running.lock().await.take() {
+            Some(server) => server
+                .shutdown
+                .send(())
+                .map_err(|_| AppError::ApiServer {
+                    reason: "already stopped".to_string(),
+                }),
+            None => Err(AppError::ApiServer {
+                reason: "not running".to_string(),
+            }),
+        }
+    }
+
+    /// Port of the running server
+    pub async fn port(&self) -> Option<u16> {
+        self.running.lock().await.as_ref().map(|server| server.port)
+    }
+}
@@ -0,0 +1,439 @@
+use std::{
+    convert::Infallible,
+    sync::atomic::AtomicBool,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc;
+
+use crate::{
+    db::{
+        conversation::Entity,
+        prompt_template::{default_template, select_prompt_template, PromptTemplate},
+        stats::record_inference,
+        Database,
+    },
+    error::AppError,
+    model::{template::ChatTurn, Model, SamplingParameters},
+};
+
+pub fn router(app_handle: AppHandle) -> Router {
+    Router::new()
+        .route("/v1/models", get(models))
+        .route("/v1/completions", post(completions))
+        .route("/v1/chat/completions", post(chat_completions))
+        .with_state(app_handle)
+}
+
+/// Sampling options shared by the completion endpoints
+#[derive(Debug, Deserialize)]
+struct GenerationOptions {
+    max_tokens: Option<usize>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    seed: Option<u64>,
+    stop: Option<StopStrings>,
+    #[serde(default)]
+    stream: bool,
+}
+
+/// The `stop` option is either a single string or a list of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StopStrings {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl GenerationOptions {
+    fn sampling(&self) -> SamplingParameters {
+        let mut sampling = SamplingParameters::default();
+        if let Some(temperature) = self.temperature {
+            sampling.sampler.temperature = temperature;
+        }
+        if let Some(top_p) = self.top_p {
+            sampling.sampler.top_p = top_p;
+        }
+        sampling.maximum_token_count = self.max_tokens;
+        sampling.seed = self.seed;
+        sampling.stop = match &self.stop {
+            Some(StopStrings::One(stop)) => vec![stop.clone()],
+            Some(StopStrings::Many(stops)) => stops.clone(),
+            None => Vec::new(),
+        };
+        sampling
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionRequest {
+    prompt: String,
+    #[serde(flatten)]
+    options: GenerationOptions,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionRequest {
+    messages: Vec<ChatMessage>,
+    #[serde(flatten)]
+    options: GenerationOptions,
+}
+
+/// The two completion endpoints only differ by the shape of their objects
+#[derive(Clone, Copy)]
+enum Endpoint {
+    Completion,
+    ChatCompletion,
+}
+
+impl Endpoint {
+    fn object(self, stream: bool) -> &'static str {
+        match (self, stream) {
+            (Endpoint::Completion, _) => "text_completion",
+            (Endpoint::ChatCompletion, false) => "chat.completion",
+            (Endpoint::ChatCompletion, true) => "chat.completion.chunk",
+        }
+    }
+
+    fn choice(self, content: &str, finish_reason: Option<&str>, stream: bool) -> Value {
+        match (self, stream) {
+            (Endpoint::Completion, _) => json!({
+                "index": 0,
+                "text": content,
+                "logprobs": null,
+                "finish_reason": finish_reason,
+            }),
+            (Endpoint::ChatCompletion, false) => json!({
+                "index": 0,
+                "message": { "role": "assistant", "content": content },
+                "finish_reason": finish_reason,
+            }),
+            (Endpoint::ChatCompletion, true) => json!({
+                "index": 0,
+                "delta": if content.is_empty() { json!({}) } else { json!({ "role": "assistant", "content": content }) },
+                "finish_reason": finish_reason,
+            }),
+        }
+    }
+}
+
+fn error_response(status: StatusCode, message: &str, code: &str) -> Response {
+    (
+        status,
+        Json(json!({ "error": { "message": message, "type": "invalid_request_error", "code": code } })),
+    )
+        .into_response()
+}
+
+fn app_error_response(err: &AppError) -> Response {
+    let status = match err {
+        AppError::ModelNotLoaded => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error_response(status, &err.to_string(), err.code())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// What the endpoints ask to predict
+enum Prompt {
+    /// Raw text, fed as is
+    Completion(String),
+    /// Conversation rendered with the template of the model config
+    Chat {
+        template: PromptTemplate,
+        history: Vec<ChatTurn>,
+        prompt: String,
+    },
+}
+
+impl Prompt {
+    /// The system messages replace the system part of the template and the last user message is
+    /// the prompt
+    fn chat(mut template: PromptTemplate, messages: Vec<ChatMessage>) -> Self {
+        let mut system = Vec::new();
+        let mut history = Vec::new();
+        for message in messages {
+            match message.role.as_str() {
+                "system" => system.push(message.content),
+                "assistant" => history.push(ChatTurn::new(Entity::Bot, message.content)),
+                _ => history.push(ChatTurn::new(Entity::User, message.content)),
+            }
+        }
+        let prompt = match history.last() {
+            Some(turn) if turn.entity == Entity::User => {
+                history.pop().map(|turn| turn.content).unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+        if !system.is_empty() {
+            template.system = format!("{}\n", system.join("\n"));
+        }
+        Prompt::Chat {
+            template,
+            history,
+            prompt,
+        }
+    }
+}
+
+/// Prompt template of the loaded model config, the default one when the config picked none
+async fn model_template(app_handle: &AppHandle) -> Result<PromptTemplate, AppError> {
+    let Some(template_name) = app_handle.state::<Model>().template_name() else {
+        return Ok(default_template());
+    };
+    let Some(state) = app_handle.try_state::<Database>() else {
+        return Err(AppError::DbNotConnected);
+    };
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    select_prompt_template(db, &template_name).await
+}
+
+/// Run the prediction, the tokens are sent through `tokens`. Nothing stops an API prediction but
+/// the client going away, which drops the receiver and fails the next send.
+fn predict(
+    model: &Model,
+    prompt: Prompt,
+    sampling: &SamplingParameters,
+    tokens: mpsc::UnboundedSender<String>,
+) -> Result<llm::InferenceStats, AppError> {
+    let stop_flag = AtomicBool::new(false);
+    let on_token = |t: &str| match tokens.send(t.to_owned()) {
+        Ok(()) => llm::InferenceFeedback::Continue,
+        Err(_) => llm::InferenceFeedback::Halt,
+    };
+    match prompt {
+        Prompt::Completion(prompt) => {
+            model.predict(None, &prompt, None, sampling, &stop_flag, on_token)
+        }
+        Prompt::Chat {
+            template,
+            history,
+            prompt,
+        } => model.chat(None, &template, &history, &prompt, sampling, &stop_flag, on_token),
+    }
+}
+
+/// Run the prediction on a blocking thread, the tokens are sent through `tokens`
+fn spawn_prediction(
+    app_handle: AppHandle,
+    prompt: Prompt,
+    sampling: SamplingParameters,
+    tokens: mpsc::UnboundedSender<String>,
+) -> tauri::async_runtime::JoinHandle<Result<llm::InferenceStats, AppError>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        predict(&app_handle.state::<Model>(), prompt, &sampling, tokens)
+    })
+}
+
+fn finish_reason(stats: &llm::InferenceStats, sampling: &SamplingParameters) -> &'static str {
+    match sampling.maximum_token_count {
+        Some(maximum_token_count) if stats.predict_tokens >= maximum_token_count => "length",
+        _ => "stop",
+    }
+}
+
+async fn models(State(app_handle): State<AppHandle>) -> Response {
+    let model = app_handle.state::<Model>();
+    let data = match (model.is_loaded(), model.model_config_name()) {
+        (true, Some(name)) => vec![json!({
+            "id": name,
+            "object": "model",
+            "created": 0,
+            "owned_by": "personal-assistant",
+        })],
+        _ => vec![],
+    };
+    Json(json!({ "object": "list", "data": data })).into_response()
+}
+
+async fn completions(
+    State(app_handle): State<AppHandle>,
+    Json(request): Json<CompletionRequest>,
+) -> Response {
+    complete(
+        app_handle,
+        Endpoint::Completion,
+        Prompt::Completion(request.prompt),
+        request.options,
+    )
+    .await
+}
+
+async fn chat_completions(
+    State(app_handle): State<AppHandle>,
+    Json(request): Json<ChatCompletionRequest>,
+) -> Response {
+    let template = match model_template(&app_handle).await {
+        Ok(template) => template,
+        Err(err) => return app_error_response(&err),
+    };
+    let prompt = Prompt::chat(template, request.messages);
+    complete(app_handle, Endpoint::ChatCompletion, prompt, request.options).await
+}
+
+async fn complete(
+    app_handle: AppHandle,
+    endpoint: Endpoint,
+    prompt: Prompt,
+    options: GenerationOptions,
+) -> Response {
+    let model_name = {
+        let model = app_handle.state::<Model>();
+        match (model.is_loaded(), model.model_config_name()) {
+            (true, Some(name)) => name,
+            _ => return app_error_response(&AppError::ModelNotLoaded),
+        }
+    };
+    tracing::info!("API completion request: {options:?}");
+    let sampling = options.sampling();
+    let id = format!("cmpl-{:x}", rand::random::<u64>());
+    let created = unix_time();
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let prediction = spawn_prediction(app_handle.clone(), prompt, sampling.clone(), tx);
+
+    if options.stream {
+        let chunk = {
+            let id = id.clone();
+            let model_name = model_name.clone();
+            move |content: &str, finish_reason: Option<&str>| {
+                json!({
+                    "id": id,
+                    "object": endpoint.object(true),
+                    "created": created,
+                    "model": model_name,
+                    "choices": [endpoint.choice(content, finish_reason, true)],
+                })
+                .to_string()
+            }
+        };
+        let token_chunk = chunk.clone();
+        let tokens = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|token| (token, rx))
+        })
+        .map(move |token| Ok::<_, Infallible>(Event::default().data(token_chunk(&token, None))));
+        let end = stream::once(async move {
+            let data = match prediction.await {
+                Ok(Ok(stats)) => {
+                    record_inference(&app_handle, &stats).await;
+                    chunk("", Some(finish_reason(&stats, &sampling)))
+                }
+                Ok(Err(err)) => json!({ "error": { "message": err.to_string(), "code": err.code() } })
+                    .to_string(),
+                Err(err) => json!({ "error": { "message": err.to_string(), "code": "internal" } })
+                    .to_string(),
+            };
+            Ok::<_, Infallible>(Event::default().data(data))
+        });
+        let done = stream::once(async { Ok::<_, Infallible>(Event::default().data("[DONE]")) });
+        return Sse::new(tokens.chain(end).chain(done)).into_response();
+    }
+
+    let stats = match prediction.await {
+        Ok(Ok(stats)) => {
+            record_inference(&app_handle, &stats).await;
+            stats
+        }
+        Ok(Err(err)) => return app_error_response(&err),
+        Err(err) => return app_error_response(&AppError::internal(err)),
+    };
+    let mut content = String::new();
+    while let Ok(token) = rx.try_recv() {
+        content.push_str(&token);
+    }
+    Json(json!({
+        "id": id,
+        "object": endpoint.object(false),
+        "created": created,
+        "model": model_name,
+        "choices": [endpoint.choice(&content, Some(finish_reason(&stats, &sampling)), false)],
+        "usage": {
+            "prompt_tokens": stats.prompt_tokens,
+            "completion_tokens": stats.predict_tokens,
+            "total_tokens": stats.prompt_tokens + stats.predict_tokens,
+        },
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::mock_engine::loaded_model;
+
+    #[test]
+    fn a_client_going_away_halts_the_prediction() {
+        let model = loaded_model("mock");
+        let prompt = || Prompt::Completion("one two three four".to_string());
+        let sampling = SamplingParameters::default();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let stats = predict(&model, prompt(), &sampling, tx).unwrap();
+        assert_eq!(stats.predict_tokens, 6);
+        // The receiver of the stream is dropped with the SSE response
+        let (tx, rx) = mpsc::unbounded_channel();
+        drop(rx);
+        let stats = predict(&model, prompt(), &sampling, tx).unwrap();
+        assert_eq!(stats.predict_tokens, 1);
+    }
+
+    #[test]
+    fn system_messages_replace_the_system_part_of_the_template() {
+        let message = |role: &str, content: &str| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+        let template = PromptTemplate {
+            name: "ChatML".to_string(),
+            ..default_template()
+        };
+        let messages = vec![
+            message("system", "Be brief."),
+            message("user", "Hi"),
+            message("assistant", "Hello"),
+            message("user", "Bye"),
+        ];
+        let Prompt::Chat {
+            template,
+            history,
+            prompt,
+        } = Prompt::chat(template, messages)
+        else {
+            panic!("not a chat");
+        };
+        assert_eq!(template.name, "ChatML");
+        assert_eq!(template.system, "Be brief.\n");
+        assert_eq!(history.len(), 2);
+        assert_eq!(prompt, "Bye");
+    }
+}
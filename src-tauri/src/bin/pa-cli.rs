@@ -0,0 +1,362 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+    sync::atomic::AtomicBool,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use personal_assistant::{
+    db::{
+        benchmark::insert_benchmark,
+        conversation::Entity,
+        logic, now_millis,
+        prompt_template::{self, PromptTemplate, DEFAULT_TEMPLATE},
+        Database,
+    },
+    error::AppError,
+    model::{
+        benchmark, template::ChatTurn, BenchmarkConfig, Model, ModelArchitecture, ModelConfig,
+        SamplingParameters, TokenizerSource,
+    },
+};
+
+/// Config of the app, its bundle identifier names the directory the app keeps its data in
+const TAURI_CONFIG: &str = include_str!("../../tauri.conf.json");
+/// Conversation id of the REPL session
+const SESSION_ID: &str = "pa-cli";
+
+#[derive(Parser)]
+#[command(name = "pa-cli", version, about = "Personal assistant from the command line")]
+struct Cli {
+    /// Data directory shared with the app, defaults to the one of the installed app
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the saved model configs
+    List,
+    /// Save a new model config
+    Add {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        path: PathBuf,
+        /// Bloom, Gpt2, GptJ, GptNeoX, Llama, Mpt or Falcon (needs a build with the `falcon`
+        /// feature)
+        #[arg(long, value_parser = ModelArchitecture::from_str, default_value = "Llama")]
+        architecture: ModelArchitecture,
+        /// Hugging Face tokenizer.json, the tokenizer embedded in the model is used otherwise
+        #[arg(long)]
+        tokenizer_file: Option<PathBuf>,
+    },
+    /// Remove a saved model config
+    Remove { name: String },
+    /// List the prompt templates
+    Templates,
+    /// Load a saved model config then run a single prompt, or chat when no prompt is given
+    Run {
+        name: String,
+        #[arg(short, long)]
+        prompt: Option<String>,
+        /// Prompt template of the chat, defaults to the one of the model config
+        #[arg(short, long)]
+        template: Option<String>,
+        /// Defaults to the load parameters saved with the model config
+        #[arg(long)]
+        context_size: Option<usize>,
+        /// Linear RoPE scale, below 1 for extended context models
+        #[arg(long)]
+        rope_scale: Option<f32>,
+        /// RoPE frequency base, above 10000 for extended context models
+        #[arg(long)]
+        rope_base: Option<usize>,
+        #[arg(long)]
+        use_gpu: bool,
+        #[arg(long)]
+        max_tokens: Option<usize>,
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Stop the answer at this string, added to the ones of the template
+        #[arg(long)]
+        stop: Vec<String>,
+    },
+    /// Load a saved model config and run the benchmark suite, the result is stored with the
+    /// benchmarks of the app
+    Bench {
+        name: String,
+        #[arg(long, default_value_t = BenchmarkConfig::default().prompt_tokens)]
+        prompt_tokens: usize,
+        #[arg(long, default_value_t = BenchmarkConfig::default().generation_tokens)]
+        generation_tokens: usize,
+        #[arg(long, default_value_t = BenchmarkConfig::default().repetitions)]
+        repetitions: usize,
+        /// Defaults to the load parameters saved with the model config
+        #[arg(long)]
+        context_size: Option<usize>,
+        #[arg(long)]
+        use_gpu: bool,
+        /// Print the samples as CSV or the result as JSON after the summary
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// Bundle identifier of the app, the same one the app resolves its data directory with
+fn app_identifier() -> Option<String> {
+    let config: serde_json::Value = serde_json::from_str(TAURI_CONFIG).ok()?;
+    config["tauri"]["bundle"]["identifier"]
+        .as_str()
+        .map(str::to_owned)
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_max_level(tracing::Level::WARN)
+        .init();
+    let cli = Cli::parse();
+    let data_dir = cli
+        .data_dir
+        .or_else(|| Some(tauri::api::path::data_dir()?.join(app_identifier()?)))
+        .unwrap_or_default();
+
+    let result = tauri::async_runtime::block_on(async move {
+        let db = Database::open(&data_dir).await?;
+        match cli.command {
+            Command::List => {
+                for model_config in logic::select_model_configs(&db).await? {
+                    println!(
+                        "{}\t{:?}\t{}",
+                        model_config.name,
+                        model_config.model_architecture,
+                        model_config.model_path.display()
+                    );
+                }
+            }
+            Command::Add {
+                name,
+                path,
+                architecture,
+                tokenizer_file,
+            } => {
+                let model_config = ModelConfig {
+                    name,
+                    model_architecture: architecture,
+                    model_path: path,
+                    tokenizer_source: match tokenizer_file {
+                        Some(tokenizer_file) => {
+                            TokenizerSource::HuggingFaceTokenizerFile(tokenizer_file)
+                        }
+                        None => TokenizerSource::Embedded,
+                    },
+                    ..Default::default()
+                };
+                let created = logic::insert_model_config(&db, model_config).await?;
+                println!("Model config added: {}", created.name);
+            }
+            Command::Remove { name } => {
+                let deleted = logic::remove_model_config(&db, &name).await?;
+                println!("Model config deleted: {}", deleted.name);
+            }
+            Command::Templates => {
+                for template in prompt_template::select_prompt_templates(&db).await? {
+                    let kind = if template.builtin { "built-in" } else { "custom" };
+                    println!("{}\t{kind}", template.name);
+                }
+            }
+            Command::Run {
+                name,
+                prompt,
+                template,
+                context_size,
+                rope_scale,
+                rope_base,
+                use_gpu,
+                max_tokens,
+                seed,
+                stop,
+            } => {
+                let model_config = logic::select_model_config(&db, &name).await?;
+                let template_name = template
+                    .or_else(|| model_config.template_name.clone())
+                    .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+                let template = prompt_template::select_prompt_template(&db, &template_name).await?;
+                // The arguments override the parameters saved with the model config
+                let mut model_params: llm::ModelParameters =
+                    model_config.load_params.clone().unwrap_or_default().into();
+                if let Some(context_size) = context_size {
+                    model_params.context_size = context_size;
+                }
+                if rope_scale.is_some() || rope_base.is_some() {
+                    let rope_overrides = model_params.rope_overrides.get_or_insert_with(Default::default);
+                    if let Some(rope_scale) = rope_scale {
+                        rope_overrides.frequency_scale = rope_scale;
+                    }
+                    if let Some(rope_base) = rope_base {
+                        rope_overrides.frequency_base = rope_base;
+                    }
+                }
+                model_params.use_gpu |= use_gpu;
+                let mut sampling = model_config.sampling.clone().unwrap_or_default();
+                if max_tokens.is_some() {
+                    sampling.maximum_token_count = max_tokens;
+                }
+                if seed.is_some() {
+                    sampling.seed = seed;
+                }
+                sampling.stop.extend(stop);
+                let model = Model::default();
+                model.set_model_config(model_config)?;
+                model.load(model_params, &AtomicBool::new(false), &mut |progress| {
+                    eprintln!("{progress:?}")
+                })?;
+                match prompt {
+                    Some(prompt) => run_prompt(&model, &prompt, &sampling)?,
+                    None => repl(&model, &template, &sampling)?,
+                }
+            }
+            Command::Bench {
+                name,
+                prompt_tokens,
+                generation_tokens,
+                repetitions,
+                context_size,
+                use_gpu,
+                format,
+            } => {
+                let model_config = logic::select_model_config(&db, &name).await?;
+                let mut model_params: llm::ModelParameters =
+                    model_config.load_params.clone().unwrap_or_default().into();
+                if let Some(context_size) = context_size {
+                    model_params.context_size = context_size;
+                }
+                model_params.use_gpu |= use_gpu;
+                let model = Model::default();
+                model.set_model_config(model_config)?;
+                model.load(model_params, &AtomicBool::new(false), &mut |progress| {
+                    eprintln!("{progress:?}")
+                })?;
+                let config = BenchmarkConfig {
+                    prompt_tokens,
+                    generation_tokens,
+                    repetitions,
+                };
+                let result = benchmark::run(
+                    &model,
+                    &config,
+                    now_millis(),
+                    &AtomicBool::new(false),
+                    &mut |done, total| eprintln!("Run {done} of {total}"),
+                )?;
+                eprintln!(
+                    "Prompt eval: {:.2} ± {:.2} tokens/s",
+                    result.prompt_eval.mean, result.prompt_eval.std_dev
+                );
+                eprintln!(
+                    "Generation: {:.2} ± {:.2} tokens/s",
+                    result.generation.mean, result.generation.std_dev
+                );
+                match result.peak_memory_bytes {
+                    Some(peak_memory_bytes) => {
+                        eprintln!("Peak memory: {} MB", peak_memory_bytes / (1024 * 1024))
+                    }
+                    None => eprintln!("Peak memory: not measured on this system"),
+                }
+                let result = insert_benchmark(&db, result).await?;
+                match format {
+                    Some(Format::Csv) => print!("{}", benchmark::to_csv(&[result])),
+                    Some(Format::Json) => println!(
+                        "{}",
+                        serde_json::to_string_pretty(&result).map_err(AppError::internal)?
+                    ),
+                    None => (),
+                }
+            }
+        }
+        Ok::<_, AppError>(())
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("pa-cli: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Print a token as soon as it is inferred
+fn print_token(t: &str) {
+    print!("{t}");
+    let _ = io::stdout().flush();
+}
+
+/// Stream the prediction to stdout and the statistics to stderr
+fn run_prompt(model: &Model, prompt: &str, sampling: &SamplingParameters) -> Result<(), AppError> {
+    let stop_flag = AtomicBool::new(false);
+    let stats = model.predict(None, prompt, None, sampling, &stop_flag, |t| {
+        print_token(t);
+        llm::InferenceFeedback::Continue
+    })?;
+    println!();
+    eprintln!("{stats}");
+    Ok(())
+}
+
+fn repl(
+    model: &Model,
+    template: &PromptTemplate,
+    sampling: &SamplingParameters,
+) -> Result<(), AppError> {
+    eprintln!("Type /reset to start over and /exit to quit");
+    let stdin = io::stdin();
+    let stop_flag = AtomicBool::new(false);
+    let mut history = Vec::new();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match line.trim() {
+            "" => continue,
+            "/exit" => return Ok(()),
+            "/reset" => {
+                model.drop_session(SESSION_ID)?;
+                history.clear();
+            }
+            input => {
+                let mut answer = String::new();
+                let stats = model.chat(
+                    Some(SESSION_ID),
+                    template,
+                    &history,
+                    input,
+                    sampling,
+                    &stop_flag,
+                    |t| {
+                        print_token(t);
+                        answer.push_str(t);
+                        llm::InferenceFeedback::Continue
+                    },
+                )?;
+                println!();
+                eprintln!("{stats}");
+                history.push(ChatTurn::new(Entity::User, input));
+                history.push(ChatTurn::new(Entity::Bot, answer));
+            }
+        }
+    }
+}
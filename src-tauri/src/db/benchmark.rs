@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use surrealdb::{engine::local::Db, Surreal};
+
+use super::Database;
+use crate::error::AppError;
+use crate::model::{benchmark, BenchmarkResult, ExportFormat};
+
+/// Store the result of a benchmark, keyed by its id
+pub async fn insert_benchmark(
+    db: &Surreal<Db>,
+    result: BenchmarkResult,
+) -> Result<BenchmarkResult, AppError> {
+    let created: Option<BenchmarkResult> = db
+        .create(("benchmark", result.benchmark_id.as_str()))
+        .content(result)
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Benchmark stored: {:?}", created.as_ref().map(|result| &result.benchmark_id));
+    created.ok_or_else(|| AppError::already_exists("Benchmark"))
+}
+
+/// Benchmarks oldest first, of the given model or of all the models
+pub async fn select_benchmarks(
+    db: &Surreal<Db>,
+    model_name: Option<String>,
+) -> Result<Vec<BenchmarkResult>, AppError> {
+    let results: Vec<BenchmarkResult> = match model_name {
+        Some(model_name) => db
+            .query("SELECT * FROM benchmark WHERE model_name = $model_name ORDER BY timestamp ASC")
+            .bind(("model_name", model_name.as_str()))
+            .await
+            .map_err(AppError::db)?
+            .take(0)
+            .map_err(AppError::db)?,
+        None => db
+            .query("SELECT * FROM benchmark ORDER BY timestamp ASC")
+            .await
+            .map_err(AppError::db)?
+            .take(0)
+            .map_err(AppError::db)?,
+    };
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn list_benchmarks(
+    model_name: Option<String>,
+    state: tauri::State<'_, Database>,
+) -> Result<Vec<BenchmarkResult>, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    select_benchmarks(db, model_name).await
+}
+
+#[tauri::command]
+pub async fn delete_benchmark(
+    benchmark_id: String,
+    state: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let deleted: Option<BenchmarkResult> = db
+        .delete(("benchmark", benchmark_id.as_str()))
+        .await
+        .map_err(AppError::db)?;
+    match deleted {
+        Some(deleted) => Ok(format!("Benchmark of {} deleted", deleted.model_name)),
+        None => Err(AppError::not_found("Benchmark")),
+    }
+}
+
+/// Write the benchmarks of the given model, or of all the models, to `path`
+#[tauri::command]
+pub async fn export_benchmarks(
+    path: PathBuf,
+    format: ExportFormat,
+    model_name: Option<String>,
+    state: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let results = select_benchmarks(db, model_name).await?;
+    let content = match format {
+        ExportFormat::Csv => benchmark::to_csv(&results),
+        ExportFormat::Json => serde_json::to_string_pretty(&results).map_err(AppError::internal)?,
+    };
+    std::fs::write(&path, content)?;
+    tracing::info!("{} benchmarks exported to {}", results.len(), path.display());
+    Ok(format!("{} benchmarks exported to {}", results.len(), path.display()))
+}
@@ -0,0 +1,248 @@
+use serde_json::json;
+use tauri::{Runtime, Window};
+
+use super::{now_millis, prompt_template::select_prompt_template, Database};
+use crate::error::AppError;
+use crate::model::Model;
+
+pub use personal_assistant_types::{Conversation, Entity, StoredMessage};
+
+#[tauri::command]
+pub async fn create_conversation<R: Runtime>(
+    win: Window<R>,
+    title: String,
+    template_name: Option<String>,
+    state: tauri::State<'_, Database>,
+) -> Result<Conversation, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let now = now_millis();
+    let conversation = Conversation {
+        conversation_id: format!("{now:x}{:04x}", rand::random::<u16>()),
+        title,
+        created_at: now,
+        updated_at: now,
+        template_name,
+    };
+    let created: Option<Conversation> = db
+        .create(("conversation", conversation.conversation_id.as_str()))
+        .content(conversation)
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Conversation created: {:#?}", created);
+    match created {
+        Some(created) => {
+            let _ = win
+                .emit("conversation_sync_event", ())
+                .map_err(|err| err.to_string());
+            Ok(created)
+        }
+        None => Err(AppError::already_exists("Conversation")),
+    }
+}
+
+#[tauri::command]
+pub async fn list_conversations(
+    state: tauri::State<'_, Database>,
+) -> Result<Vec<Conversation>, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let conversations: Vec<Conversation> = db
+        .query("SELECT * FROM conversation ORDER BY updated_at DESC")
+        .await
+        .map_err(AppError::db)?
+        .take(0)
+        .map_err(AppError::db)?;
+    Ok(conversations)
+}
+
+#[tauri::command]
+pub async fn rename_conversation<R: Runtime>(
+    win: Window<R>,
+    conversation_id: String,
+    title: String,
+    state: tauri::State<'_, Database>,
+) -> Result<Conversation, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    // Updating a missing record would create it
+    let existing: Option<Conversation> = db
+        .select(("conversation", conversation_id.as_str()))
+        .await
+        .map_err(AppError::db)?;
+    if existing.is_none() {
+        return Err(AppError::not_found("Conversation"));
+    }
+    let renamed: Option<Conversation> = db
+        .update(("conversation", conversation_id.as_str()))
+        .merge(json!({ "title": title, "updated_at": now_millis() }))
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Conversation renamed: {:#?}", renamed);
+    match renamed {
+        Some(renamed) => {
+            let _ = win
+                .emit("conversation_sync_event", ())
+                .map_err(|err| err.to_string());
+            Ok(renamed)
+        }
+        None => Err(AppError::not_found("Conversation")),
+    }
+}
+
+/// Pick the prompt template of the conversation, `None` falls back to the model config one
+#[tauri::command]
+pub async fn set_conversation_template<R: Runtime>(
+    win: Window<R>,
+    conversation_id: String,
+    template_name: Option<String>,
+    state: tauri::State<'_, Database>,
+) -> Result<Conversation, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    if let Some(template_name) = template_name.as_deref() {
+        select_prompt_template(db, template_name).await?;
+    }
+    // Updating a missing record would create it
+    let existing: Option<Conversation> = db
+        .select(("conversation", conversation_id.as_str()))
+        .await
+        .map_err(AppError::db)?;
+    if existing.is_none() {
+        return Err(AppError::not_found("Conversation"));
+    }
+    let updated: Option<Conversation> = db
+        .update(("conversation", conversation_id.as_str()))
+        .merge(json!({ "template_name": template_name }))
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Conversation template set: {:#?}", updated);
+    match updated {
+        Some(updated) => {
+            let _ = win
+                .emit("conversation_sync_event", ())
+                .map_err(|err| err.to_string());
+            Ok(updated)
+        }
+        None => Err(AppError::not_found("Conversation")),
+    }
+}
+
+#[tauri::command]
+pub async fn delete_conversation<R: Runtime>(
+    win: Window<R>,
+    conversation_id: String,
+    state: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let deleted: Option<Conversation> = db
+        .delete(("conversation", conversation_id.as_str()))
+        .await
+        .map_err(AppError::db)?;
+    db.query("DELETE message WHERE conversation_id = $conversation_id")
+        .bind(("conversation_id", conversation_id.as_str()))
+        .await
+        .map_err(AppError::db)?
+        .check()
+        .map_err(AppError::db)?;
+    tracing::info!("Conversation deleted: {:#?}", deleted);
+    match deleted {
+        Some(deleted) => {
+            let _ = win
+                .emit("conversation_sync_event", ())
+                .map_err(|err| err.to_string());
+            Ok(format!("Conversation deleted: {}", deleted.title))
+        }
+        None => Err(AppError::not_found("Conversation")),
+    }
+}
+
+#[tauri::command]
+pub async fn load_conversation(
+    conversation_id: String,
+    state: tauri::State<'_, Database>,
+) -> Result<Vec<StoredMessage>, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let messages: Vec<StoredMessage> = db
+        .query("SELECT * FROM message WHERE conversation_id = $conversation_id ORDER BY position ASC")
+        .bind(("conversation_id", conversation_id.as_str()))
+        .await
+        .map_err(AppError::db)?
+        .take(0)
+        .map_err(AppError::db)?;
+    tracing::info!("Loaded {} messages of conversation {conversation_id}", messages.len());
+    Ok(messages)
+}
+
+/// Store a message, the bot messages are tagged with the name of the loaded model config
+#[tauri::command]
+pub async fn add_message<R: Runtime>(
+    win: Window<R>,
+    mut message: StoredMessage,
+    state: tauri::State<'_, Database>,
+    model_state: tauri::State<'_, Model>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    if message.entity == Entity::Bot && message.model_name.is_none() {
+        message.model_name = model_state.model_config_name();
+    }
+    let message_id = format!("{}_{:06}", message.conversation_id, message.position);
+    let conversation_id = message.conversation_id.clone();
+    let created: Option<StoredMessage> = db
+        .create(("message", message_id.as_str()))
+        .content(message)
+        .await
+        .map_err(AppError::db)?;
+    if created.is_none() {
+        return Err(AppError::already_exists(format!("Message {message_id}")));
+    }
+    db.query("UPDATE type::thing('conversation', $conversation_id) SET updated_at = $now")
+        .bind(("conversation_id", conversation_id.as_str()))
+        .bind(("now", now_millis()))
+        .await
+        .map_err(AppError::db)?
+        .check()
+        .map_err(AppError::db)?;
+    let _ = win
+        .emit("conversation_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(format!("Message {message_id} added"))
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use notify::{event::ModifyKind, EventKind, RecursiveMode, Watcher};
+use serde_json::json;
+use surrealdb::{engine::local::Db, Surreal};
+use tauri::{App, AppHandle, Manager};
+use tokio::sync::mpsc;
+
+use super::{
+    logic::{insert_model_config, select_model_configs},
+    Database,
+};
+use crate::{
+    error::AppError,
+    model::{inspect, ModelArchitecture, ModelConfig},
+};
+
+/// Extensions of the files looked at by the scanner
+pub const MODEL_EXTENSIONS: &[&str] = &["bin", "ggml"];
+/// Filesystem events coming in this window trigger a single scan
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+pub use personal_assistant_types::{ModelDirectory, ScanReport};
+
+/// Watches the model directories while the app runs
+#[derive(Default)]
+pub struct ModelLibrary {
+    // Dropping the watcher stops it
+    watcher: Mutex<Option<notify::RecommendedWatcher>>,
+}
+
+impl ModelLibrary {
+    pub fn init(app: &App) -> Result<(), AppError> {
+        app.manage(ModelLibrary::default());
+        Ok(())
+    }
+
+    /// Watch the directories, replacing the previous watcher. Each burst of files appearing or
+    /// disappearing triggers a scan followed by a `db_sync_event`.
+    pub fn watch(
+        &self,
+        app_handle: AppHandle,
+        directories: &[ModelDirectory],
+    ) -> Result<(), AppError> {
+        let (events, mut events_rx) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) => {
+                    if let EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_)) = event.kind
+                    {
+                        let _ = events.send(());
+                    }
+                }
+                Err(err) => tracing::warn!("Model library watch error: {err}"),
+            })
+            .map_err(AppError::io)?;
+        for directory in directories {
+            if let Err(err) = watcher.watch(&directory.path, RecursiveMode::Recursive) {
+                tracing::warn!("Can not watch {}: {err}", directory.path.display());
+            }
+        }
+        *self.watcher.lock()? = Some(watcher);
+        // The task ends with the watcher, it holds the sender
+        tauri::async_runtime::spawn(async move {
+            while events_rx.recv().await.is_some() {
+                tokio::time::sleep(WATCH_DEBOUNCE).await;
+                while events_rx.try_recv().is_ok() {}
+                if let Err(err) = rescan(&app_handle).await {
+                    tracing::error!("Model library scan failed: {err}");
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Scan and watch the stored directories, called once the database is open
+pub async fn start(app_handle: &AppHandle) -> Result<(), AppError> {
+    let directories = {
+        let database = app_handle.state::<Database>();
+        let db = database.db.lock().await;
+        let db = match db.as_ref() {
+            Some(db) => db,
+            None => return Err(AppError::DbNotConnected),
+        };
+        scan(db).await?;
+        select_model_directories(db).await?
+    };
+    app_handle
+        .state::<ModelLibrary>()
+        .watch(app_handle.clone(), &directories)
+}
+
+async fn rescan(app_handle: &AppHandle) -> Result<(), AppError> {
+    let database = app_handle.state::<Database>();
+    let db = database.db.lock().await;
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let report = scan(db).await?;
+    if !report.is_empty() {
+        tracing::info!("Model library changed: {report:#?}");
+        app_handle
+            .emit_all("db_sync_event", ())
+            .map_err(AppError::internal)?;
+    }
+    Ok(())
+}
+
+pub async fn select_model_directories(db: &Surreal<Db>) -> Result<Vec<ModelDirectory>, AppError> {
+    let directories: Vec<ModelDirectory> = db
+        .select("model_directory")
+        .await
+        .map_err(AppError::db)?;
+    Ok(directories)
+}
+
+pub async fn insert_model_directory(
+    db: &Surreal<Db>,
+    path: PathBuf,
+) -> Result<ModelDirectory, AppError> {
+    if !path.is_dir() {
+        return Err(AppError::invalid_input(
+            "model directory",
+            format!("{} is not a directory", path.display()),
+        ));
+    }
+    let id = path.to_string_lossy().to_string();
+    let created: Option<ModelDirectory> = db
+        .create(("model_directory", id.as_str()))
+        .content(ModelDirectory { path })
+        .await
+        .map_err(AppError::db)?;
+    created.ok_or_else(|| AppError::already_exists(format!("Model directory {id}")))
+}
+
+pub async fn remove_model_directory(
+    db: &Surreal<Db>,
+    path: &Path,
+) -> Result<ModelDirectory, AppError> {
+    let id = path.to_string_lossy().to_string();
+    let deleted: Option<ModelDirectory> = db
+        .delete(("model_directory", id.as_str()))
+        .await
+        .map_err(AppError::db)?;
+    deleted.ok_or_else(|| AppError::not_found(format!("Model directory {id}")))
+}
+
+/// Model files under `directory`, with the architecture read from their header. The files that
+/// are not models llm can load are skipped.
+pub fn find_model_files(directory: &Path) -> Vec<(PathBuf, ModelArchitecture)> {
+    let mut found = Vec::new();
+    let mut pending = vec![directory.to_owned()];
+    while let Some(directory) = pending.pop() {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(err) => {
+                tracing::warn!("Can not read {}: {err}", directory.display());
+                continue;
+            }
+        };
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let is_model_file = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map_or(false, |extension| MODEL_EXTENSIONS.contains(&extension));
+            if !is_model_file {
+                continue;
+            }
+            match inspect::inspect(&path) {
+                Ok(metadata) => found.push((path, metadata.architecture)),
+                Err(err) => tracing::info!("Skipping {}: {err}", path.display()),
+            }
+        }
+    }
+    found.sort_by(|(path, _), (other, _)| path.cmp(other));
+    found
+}
+
+/// Name of a new model config, the file stem with a number when it is taken
+fn unique_name(path: &Path, taken: &HashSet<String>) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "model".to_string());
+    (1..)
+        .map(|index| match index {
+            1 => stem.clone(),
+            _ => format!("{stem} ({index})"),
+        })
+        .find(|name| !taken.contains(name))
+        .unwrap_or_default()
+}
+
+/// Add a model config for each new file of the model directories and flag the model configs
+/// whose file is missing
+pub async fn scan(db: &Surreal<Db>) -> Result<ScanReport, AppError> {
+    let mut report = ScanReport::default();
+    let model_configs = select_model_configs(db).await?;
+    let mut names: HashSet<String> = model_configs
+        .iter()
+        .map(|model_config| model_config.name.clone())
+        .collect();
+    let paths: HashSet<PathBuf> = model_configs
+        .iter()
+        .map(|model_config| model_config.model_path.clone())
+        .collect();
+
+    for directory in select_model_directories(db).await? {
+        let directory = directory.path;
+        let found = tauri::async_runtime::spawn_blocking(move || find_model_files(&directory))
+            .await
+            .map_err(AppError::internal)?;
+        for (path, architecture) in found {
+            if paths.contains(&path) {
+                continue;
+            }
+            let name = unique_name(&path, &names);
+            let created = insert_model_config(
+                db,
+                ModelConfig {
+                    name: name.clone(),
+                    model_architecture: architecture,
+                    model_path: path,
+                    ..Default::default()
+                },
+            )
+            .await?;
+            names.insert(name);
+            report.added.push(created.name);
+        }
+    }
+
+    for model_config in model_configs {
+        let missing = !model_config.model_path.is_file();
+        if missing == model_config.missing {
+            continue;
+        }
+        let _: Option<ModelConfig> = db
+            .update(("model_config", model_config.name.as_str()))
+            .merge(json!({ "missing": missing }))
+            .await
+            .map_err(AppError::db)?;
+        match missing {
+            true => report.missing.push(model_config.name),
+            false => report.restored.push(model_config.name),
+        }
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn list_model_directories(
+    state: tauri::State<'_, Database>,
+) -> Result<Vec<ModelDirectory>, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    select_model_directories(db).await
+}
+
+#[tauri::command]
+pub async fn add_model_directory(
+    app_handle: AppHandle,
+    path: PathBuf,
+    state: tauri::State<'_, Database>,
+    library: tauri::State<'_, ModelLibrary>,
+) -> Result<ScanReport, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let created = insert_model_directory(db, path).await?;
+    tracing::info!("Model directory added: {}", created.path.display());
+    library.watch(app_handle.clone(), &select_model_directories(db).await?)?;
+    let report = scan(db).await?;
+    app_handle
+        .emit_all("db_sync_event", ())
+        .map_err(AppError::internal)?;
+    Ok(report)
+}
+
+/// Stop scanning a directory, the model configs found in it are kept
+#[tauri::command]
+pub async fn delete_model_directory(
+    app_handle: AppHandle,
+    path: PathBuf,
+    state: tauri::State<'_, Database>,
+    library: tauri::State<'_, ModelLibrary>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let deleted = remove_model_directory(db, &path).await?;
+    library.watch(app_handle.clone(), &select_model_directories(db).await?)?;
+    app_handle
+        .emit_all("db_sync_event", ())
+        .map_err(AppError::internal)?;
+    Ok(format!("Model directory removed: {}", deleted.path.display()))
+}
+
+#[tauri::command]
+pub async fn scan_model_library(
+    app_handle: AppHandle,
+    state: tauri::State<'_, Database>,
+) -> Result<ScanReport, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let report = scan(db).await?;
+    app_handle
+        .emit_all("db_sync_event", ())
+        .map_err(AppError::internal)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_names_get_a_number() {
+        let taken = HashSet::from(["llama-7b".to_string(), "llama-7b (2)".to_string()]);
+        assert_eq!(unique_name(Path::new("/models/mpt-7b.bin"), &taken), "mpt-7b");
+        assert_eq!(unique_name(Path::new("/other/llama-7b.bin"), &taken), "llama-7b (3)");
+    }
+
+    #[test]
+    fn scanner_skips_files_that_are_not_models() {
+        let directory = std::env::temp_dir().join(format!("pa-library-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        std::fs::write(directory.join("notes.bin"), b"not a model").unwrap();
+        std::fs::write(directory.join("nested").join("readme.md"), b"# Models").unwrap();
+        assert!(find_model_files(&directory).is_empty());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
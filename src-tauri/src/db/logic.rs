@@ -1,15 +1,188 @@
-use crate::model::ModelConfig;
+use crate::{
+    error::AppError,
+    model::{check_tokenizer_source, Model, ModelConfig},
+};
 
-use super::Database;
-// use surrealdb::{engine::local::File, Surreal};
-use surrealdb::{engine::local::Mem, Surreal};
+use super::{library, prompt_template::select_prompt_template, Database};
+use std::path::PathBuf;
+use serde_json::{json, Value};
+use surrealdb::{engine::local::Db, Surreal};
 use tauri::{Runtime, Window};
 
+/// Fields of a model config a patch can not change, `name` goes through a rename, `missing`
+/// is set by the model library and `lora_adapter_sets` by the model loading
+const READ_ONLY_FIELDS: &[&str] = &["name", "missing", "lora_adapter_sets"];
+/// Number of LoRA adapter sets remembered per model config
+const MAX_LORA_ADAPTER_SETS: usize = 5;
+
+/// Store a new model config, keyed by its name
+pub async fn insert_model_config(
+    db: &Surreal<Db>,
+    model_config: ModelConfig,
+) -> Result<ModelConfig, AppError> {
+    check_tokenizer_source(&model_config.tokenizer_source, &model_config.model_path)?;
+    let created: Option<ModelConfig> = db
+        .create(("model_config", model_config.name.as_str()))
+        .content(model_config)
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Model config added: {:#?}", created);
+    created.ok_or_else(|| AppError::already_exists("Model config"))
+}
+
+pub async fn remove_model_config(db: &Surreal<Db>, name: &str) -> Result<ModelConfig, AppError> {
+    let deleted: Option<ModelConfig> = db
+        .delete(("model_config", name))
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Model config deleted: {:#?}", deleted);
+    deleted.ok_or_else(|| AppError::ConfigNotFound {
+        name: name.to_owned(),
+    })
+}
+
+pub async fn select_model_config(db: &Surreal<Db>, name: &str) -> Result<ModelConfig, AppError> {
+    let model_config: Option<ModelConfig> = db
+        .select(("model_config", name))
+        .await
+        .map_err(AppError::db)?;
+    model_config.ok_or_else(|| AppError::ConfigNotFound {
+        name: name.to_owned(),
+    })
+}
+
+/// Apply the fields of `patch` over `model_config`, the other fields are kept
+pub fn apply_patch(model_config: &ModelConfig, patch: &Value) -> Result<ModelConfig, AppError> {
+    let patch = patch
+        .as_object()
+        .ok_or_else(|| AppError::invalid_input("model config patch", "it should be an object"))?;
+    let mut merged = serde_json::to_value(model_config).map_err(AppError::internal)?;
+    let fields = merged
+        .as_object_mut()
+        .ok_or_else(|| AppError::internal("a model config is not an object"))?;
+    for (field, value) in patch {
+        if READ_ONLY_FIELDS.contains(&field.as_str()) {
+            return Err(AppError::invalid_input(
+                field.as_str(),
+                "it can not be changed by an update",
+            ));
+        }
+        if !fields.contains_key(field) {
+            return Err(AppError::invalid_input(field.as_str(), "unknown model config field"));
+        }
+        fields.insert(field.clone(), value.clone());
+    }
+    let mut updated: ModelConfig = serde_json::from_value(merged)
+        .map_err(|err| AppError::invalid_input("model config patch", err.to_string()))?;
+    updated.missing = !updated.model_path.is_file();
+    Ok(updated)
+}
+
+pub async fn update_model_config_fields(
+    db: &Surreal<Db>,
+    name: &str,
+    patch: &Value,
+) -> Result<ModelConfig, AppError> {
+    let model_config = select_model_config(db, name).await?;
+    let updated = apply_patch(&model_config, patch)?;
+    if patch.get("tokenizer_source").is_some() {
+        check_tokenizer_source(&updated.tokenizer_source, &updated.model_path)?;
+    }
+    if let Some(template_name) = updated.template_name.as_deref() {
+        select_prompt_template(db, template_name).await?;
+    }
+    let updated: Option<ModelConfig> = db
+        .update(("model_config", name))
+        .content(updated)
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Model config updated: {:#?}", updated);
+    updated.ok_or_else(|| AppError::ConfigNotFound {
+        name: name.to_owned(),
+    })
+}
+
+/// Put the adapter set first, it is only remembered once
+fn remember_lora_adapters(sets: &mut Vec<Vec<PathBuf>>, adapters: &[PathBuf]) {
+    sets.retain(|set| set != adapters);
+    sets.insert(0, adapters.to_vec());
+    sets.truncate(MAX_LORA_ADAPTER_SETS);
+}
+
+/// Record the LoRA adapters a model config was loaded with
+pub async fn record_lora_adapters(
+    db: &Surreal<Db>,
+    name: &str,
+    adapters: &[PathBuf],
+) -> Result<ModelConfig, AppError> {
+    let mut model_config = select_model_config(db, name).await?;
+    remember_lora_adapters(&mut model_config.lora_adapter_sets, adapters);
+    let updated: Option<ModelConfig> = db
+        .update(("model_config", name))
+        .merge(json!({ "lora_adapter_sets": model_config.lora_adapter_sets }))
+        .await
+        .map_err(AppError::db)?;
+    updated.ok_or_else(|| AppError::ConfigNotFound {
+        name: name.to_owned(),
+    })
+}
+
+/// Rename a model config, the messages answered by it, its inference stats and its benchmarks
+/// follow the new name
+pub async fn rename_model_config_record(
+    db: &Surreal<Db>,
+    name: &str,
+    new_name: &str,
+) -> Result<ModelConfig, AppError> {
+    if new_name.trim().is_empty() {
+        return Err(AppError::invalid_input("model config name", "the name is empty"));
+    }
+    let mut model_config = select_model_config(db, name).await?;
+    if name == new_name {
+        return Ok(model_config);
+    }
+    let existing: Option<ModelConfig> = db
+        .select(("model_config", new_name))
+        .await
+        .map_err(AppError::db)?;
+    if existing.is_some() {
+        return Err(AppError::already_exists(format!("Model config {new_name}")));
+    }
+    model_config.name = new_name.to_owned();
+    db.query(
+        "BEGIN TRANSACTION;
+        CREATE type::thing('model_config', $new_name) CONTENT $model_config;
+        DELETE type::thing('model_config', $name);
+        UPDATE message SET model_name = $new_name WHERE model_name = $name;
+        UPDATE inference_stats SET model_name = $new_name WHERE model_name = $name;
+        UPDATE benchmark SET model_name = $new_name WHERE model_name = $name;
+        COMMIT TRANSACTION;",
+    )
+    .bind(("name", name))
+    .bind(("new_name", new_name))
+    .bind(("model_config", &model_config))
+    .await
+    .map_err(AppError::db)?
+    .check()
+    .map_err(AppError::db)?;
+    tracing::info!("Model config {name} renamed to {new_name}");
+    Ok(model_config)
+}
+
+pub async fn select_model_configs(db: &Surreal<Db>) -> Result<Vec<ModelConfig>, AppError> {
+    let model_configs: Vec<ModelConfig> = db
+        .select("model_config")
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Get all model configs {:#?}", model_configs);
+    Ok(model_configs)
+}
+
 #[tauri::command]
 pub async fn connect(
     app_handle: tauri::AppHandle,
     state: tauri::State<'_, Database>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     // Check if there is a connection
     if state.db.lock().await.is_some() {
         return Ok("Database already connected".to_owned());
@@ -17,21 +190,17 @@ pub async fn connect(
     // Get the data directory
     let app_data_dir = app_handle
         .path_resolver()
-        .app_local_data_dir()
+        .app_data_dir()
         .unwrap_or_default();
     tracing::info!("App data dir is: {}", app_data_dir.display());
     // Connect to the local storage
-    // let db = Surreal::new::<File>(format!("{}/db", app_data_dir.display()).as_str())
-    let db = Surreal::new::<Mem>(())
-        .await
-        .map_err(|err| err.to_string())?;
-    // Select a specific namespace / database
-    db.use_ns("my_ns")
-        .use_db("my_db")
-        .await
-        .map_err(|err| err.to_string())?;
+    let db = Database::open(&app_data_dir).await?;
     // Set the database
     *state.db.lock().await = Some(db);
+    // The library was not started when the database failed to open at startup
+    if let Err(err) = library::start(&app_handle).await {
+        tracing::error!("Model library setup failed: {err}");
+    }
     Ok("Connected to the database".to_owned())
 }
 
@@ -40,31 +209,20 @@ pub async fn add_model_config<R: Runtime>(
     win: Window<R>,
     model_config: ModelConfig,
     state: tauri::State<'_, Database>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     // Get the database
     let db = state.db.lock().await;
     // Check if it exists
     let db = match db.as_ref() {
         Some(db) => db,
-        None => return Err("Database not connected, please reconnect to the database".to_string()),
+        None => return Err(AppError::DbNotConnected),
     };
     // Create a new model_config
-    let created: Option<ModelConfig> = db
-        .create(("model_config", model_config.name.as_str()))
-        .content(model_config)
-        .await
-        .map_err(|err| err.to_string())?;
-    tracing::info!("Model config added: {:#?}", created);
-    match created {
-        Some(created) => {
-            let _ = win
-                .emit("db_sync_event", ())
-                .map_err(|err| err.to_string());
-            Ok(format!("Model config added: {created:#?}"))
-        },
-        None => Err("Model config already exists".to_string()),
-    }
-
+    let created = insert_model_config(db, model_config).await?;
+    let _ = win
+        .emit("db_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(format!("Model config added: {created:#?}"))
 }
 
 #[tauri::command]
@@ -72,48 +230,248 @@ pub async fn delete_model_config<R: Runtime>(
     win: Window<R>,
     name: String,
     state: tauri::State<'_, Database>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     // Get the database
     let db = state.db.lock().await;
     // Check if it exists
     let db = match db.as_ref() {
         Some(db) => db,
-        None => return Err("Database not connected, please reconnect to the database".to_string()),
+        None => return Err(AppError::DbNotConnected),
     };
-    // Create a new model_config
-    let deleted: Option<ModelConfig> = db
-        .delete(("model_config", name.as_str()))
-        .await
-        .map_err(|err| err.to_string())?;
-    tracing::info!("Model config deleted: {:#?}", deleted);
-    match deleted {
-        Some(deleted) => {
-            let _ = win
-                .emit("db_sync_event", ())
-                .map_err(|err| err.to_string());
-            Ok(format!("Model config deleted: {deleted:#?}"))
-        },
-        None => Err("Model config not found".to_string()),
-    }
+    // Delete the model_config
+    let deleted = remove_model_config(db, name.as_str()).await?;
+    let _ = win
+        .emit("db_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(format!("Model config deleted: {deleted:#?}"))
+}
 
+/// Pick the default prompt template of a model config
+#[tauri::command]
+pub async fn set_model_config_template<R: Runtime>(
+    win: Window<R>,
+    name: String,
+    template_name: Option<String>,
+    state: tauri::State<'_, Database>,
+) -> Result<ModelConfig, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    if let Some(template_name) = template_name.as_deref() {
+        select_prompt_template(db, template_name).await?;
+    }
+    // Updating a missing record would create it
+    select_model_config(db, &name).await?;
+    let updated: Option<ModelConfig> = db
+        .update(("model_config", name.as_str()))
+        .merge(json!({ "template_name": template_name }))
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Model config template set: {:#?}", updated);
+    let updated = updated.ok_or_else(|| AppError::ConfigNotFound { name })?;
+    let _ = win
+        .emit("db_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(updated)
 }
 
 #[tauri::command]
 pub async fn get_model_configs(
     state: tauri::State<'_, Database>,
-) -> Result<Vec<ModelConfig>, String> {
+) -> Result<Vec<ModelConfig>, AppError> {
     // Get the database
     let db = state.db.lock().await;
     // Check if it exists
     let db = match db.as_ref() {
         Some(db) => db,
-        None => return Err("Database not connected, please reconnect to the database".to_string()),
+        None => return Err(AppError::DbNotConnected),
     };
-    // Create a new model_config
-    let model_configs: Vec<ModelConfig> = db
-        .select("model_config")
-        .await
-        .map_err(|err| err.to_string())?;
-    tracing::info!("Get all model configs {:#?}", model_configs);
-    Ok(model_configs)
+    // Get all the model_configs
+    select_model_configs(db).await
+}
+
+/// Change some fields of a model config, e.g. `{ "model_path": "..." }`
+#[tauri::command]
+pub async fn update_model_config<R: Runtime>(
+    win: Window<R>,
+    name: String,
+    patch: Value,
+    state: tauri::State<'_, Database>,
+    model_state: tauri::State<'_, Model>,
+) -> Result<ModelConfig, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let updated = update_model_config_fields(db, &name, &patch).await?;
+    // The next load of the selected model config uses the new fields
+    if model_state.model_config_name().as_deref() == Some(name.as_str()) {
+        model_state.set_model_config(updated.clone())?;
+    }
+    let _ = win
+        .emit("db_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn rename_model_config<R: Runtime>(
+    win: Window<R>,
+    name: String,
+    new_name: String,
+    state: tauri::State<'_, Database>,
+    model_state: tauri::State<'_, Model>,
+) -> Result<ModelConfig, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let renamed = rename_model_config_record(db, &name, &new_name).await?;
+    if model_state.model_config_name().as_deref() == Some(name.as_str()) {
+        model_state.set_model_config(renamed.clone())?;
+    }
+    let _ = win
+        .emit("db_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{benchmark::insert_benchmark, migration},
+        model::{BenchmarkResult, InferenceRecord, ModelArchitecture, TokenizerSource},
+    };
+    use surrealdb::engine::local::Mem;
+
+    /// In-memory database with the migrations applied
+    async fn memory_db() -> Surreal<Db> {
+        let db = Surreal::new::<Mem>(()).await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+        migration::migrate(&db).await.unwrap();
+        db
+    }
+
+    /// Model names of the records of `table`
+    async fn model_names(db: &Surreal<Db>, table: &str) -> Vec<String> {
+        let mut response = db
+            .query("SELECT VALUE model_name FROM type::table($table)")
+            .bind(("table", table))
+            .await
+            .unwrap();
+        response.take(0).unwrap()
+    }
+
+    fn model_config() -> ModelConfig {
+        ModelConfig {
+            name: "llama".to_string(),
+            model_path: PathBuf::from("/models/llama.bin"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn patch_only_changes_the_given_fields() {
+        let updated = apply_patch(
+            &model_config(),
+            &json!({ "model_path": "/models/mpt.bin", "model_architecture": "Mpt" }),
+        )
+        .unwrap();
+        assert_eq!(updated.name, "llama");
+        assert_eq!(updated.model_path, PathBuf::from("/models/mpt.bin"));
+        assert_eq!(updated.model_architecture, ModelArchitecture::Mpt);
+        assert_eq!(updated.tokenizer_source, TokenizerSource::Embedded);
+        assert!(updated.missing);
+    }
+
+    #[test]
+    fn patch_sets_the_default_parameters() {
+        let updated = apply_patch(
+            &model_config(),
+            &json!({
+                "load_params": { "prefer_mmap": true, "context_size": 4096, "lora_adapters": null, "use_gpu": true, "gpu_layers": 20 },
+                "sampling": { "sampler": { "top_k": 20, "top_p": 0.9, "repeat_penalty": 1.1, "temperature": 0.2, "repetition_penalty_last_n": 64 }, "maximum_token_count": 128, "seed": 7 },
+            }),
+        )
+        .unwrap();
+        let load_params = updated.load_params.unwrap();
+        assert_eq!(load_params.context_size, 4096);
+        assert_eq!(load_params.gpu_layers, Some(20));
+        let sampling = updated.sampling.unwrap();
+        assert_eq!(sampling.sampler.top_k, 20);
+        assert_eq!(sampling.seed, Some(7));
+        assert!(sampling.stop.is_empty());
+    }
+
+    #[test]
+    fn patch_rejects_the_name_and_unknown_fields() {
+        for patch in [json!({ "name": "mpt" }), json!({ "path": "/models/mpt.bin" }), json!([])] {
+            assert!(matches!(
+                apply_patch(&model_config(), &patch),
+                Err(AppError::InvalidInput { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn patch_rejects_invalid_values() {
+        let result = apply_patch(&model_config(), &json!({ "model_architecture": "Unknown" }));
+        assert!(matches!(result, Err(AppError::InvalidInput { .. })));
+    }
+
+    #[test]
+    fn recent_lora_adapter_sets_come_first() {
+        let set = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let mut sets = vec![set(&["a.bin"]), set(&["b.bin", "c.bin"])];
+        remember_lora_adapters(&mut sets, &set(&["b.bin", "c.bin"]));
+        assert_eq!(sets, vec![set(&["b.bin", "c.bin"]), set(&["a.bin"])]);
+        for index in 0..MAX_LORA_ADAPTER_SETS {
+            remember_lora_adapters(&mut sets, &set(&[format!("{index}.bin").as_str()]));
+        }
+        assert_eq!(sets.len(), MAX_LORA_ADAPTER_SETS);
+        assert_eq!(sets[0], set(&["4.bin"]));
+    }
+
+    #[test]
+    fn renamed_model_configs_keep_their_records() {
+        tauri::async_runtime::block_on(async {
+            let db = memory_db().await;
+            insert_model_config(&db, model_config()).await.unwrap();
+            let record = InferenceRecord {
+                model_name: "llama".to_string(),
+                ..Default::default()
+            };
+            let _: Vec<InferenceRecord> =
+                db.create("inference_stats").content(record).await.unwrap();
+            let result = BenchmarkResult {
+                benchmark_id: "b1".to_string(),
+                model_name: "llama".to_string(),
+                ..Default::default()
+            };
+            insert_benchmark(&db, result).await.unwrap();
+
+            let renamed = rename_model_config_record(&db, "llama", "llama-2").await.unwrap();
+            assert_eq!(renamed.name, "llama-2");
+            assert!(select_model_config(&db, "llama").await.is_err());
+            assert_eq!(model_names(&db, "inference_stats").await, vec!["llama-2"]);
+            assert_eq!(model_names(&db, "benchmark").await, vec!["llama-2"]);
+        });
+    }
+
+    #[test]
+    fn lora_adapter_sets_are_not_patched() {
+        let err = apply_patch(&model_config(), &json!({ "lora_adapter_sets": [] })).unwrap_err();
+        assert!(matches!(err, AppError::InvalidInput { .. }));
+    }
 }
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use surrealdb::{engine::local::Db, Surreal};
+
+use crate::error::AppError;
+
+/// Schema migrations as (name, query), the schema version is the number of applied migrations.
+/// Only append to this list, an applied migration is never run again.
+const MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "model_config table",
+        "DEFINE TABLE model_config SCHEMALESS;
+        DEFINE INDEX model_config_name ON TABLE model_config COLUMNS name UNIQUE;",
+    ),
+    (
+        "conversation and message tables",
+        "DEFINE TABLE conversation SCHEMALESS;
+        DEFINE TABLE message SCHEMALESS;
+        DEFINE INDEX message_conversation ON TABLE message COLUMNS conversation_id;",
+    ),
+    (
+        "prompt_template table",
+        "DEFINE TABLE prompt_template SCHEMALESS;
+        DEFINE INDEX prompt_template_name ON TABLE prompt_template COLUMNS name UNIQUE;",
+    ),
+    (
+        "model_directory table and missing model files",
+        "DEFINE TABLE model_directory SCHEMALESS;
+        UPDATE model_config SET missing = false WHERE missing = NONE;",
+    ),
+    (
+        "model_config default load and sampling parameters",
+        "UPDATE model_config SET load_params = null WHERE load_params = NONE;
+        UPDATE model_config SET sampling = null WHERE sampling = NONE;",
+    ),
+    (
+        "model_config LoRA adapter sets",
+        "UPDATE model_config SET lora_adapter_sets = [] WHERE lora_adapter_sets = NONE;",
+    ),
+    (
+        "inference_stats table",
+        "DEFINE TABLE inference_stats SCHEMALESS;
+        DEFINE INDEX inference_stats_model ON TABLE inference_stats COLUMNS model_name;",
+    ),
+    (
+        "benchmark table",
+        "DEFINE TABLE benchmark SCHEMALESS;
+        DEFINE INDEX benchmark_model ON TABLE benchmark COLUMNS model_name;",
+    ),
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Schema {
+    version: usize,
+}
+
+/// Apply the migrations newer than the version stored in `meta:schema`
+pub async fn migrate(db: &Surreal<Db>) -> Result<(), AppError> {
+    let schema: Option<Schema> = db
+        .select(("meta", "schema"))
+        .await
+        .map_err(AppError::db)?;
+    let schema = schema.unwrap_or_default();
+    tracing::info!("Database schema version: {}", schema.version);
+    for (version, (name, query)) in MIGRATIONS.iter().enumerate().skip(schema.version) {
+        tracing::info!("Applying migration {}: {name}", version + 1);
+        db.query(*query)
+            .await
+            .map_err(AppError::db)?
+            .check()
+            .map_err(|err| AppError::Db {
+                reason: format!("Migration {name} failed: {err}"),
+            })?;
+        let _: Option<Schema> = db
+            .update(("meta", "schema"))
+            .content(Schema {
+                version: version + 1,
+            })
+            .await
+            .map_err(AppError::db)?;
+    }
+    Ok(())
+}
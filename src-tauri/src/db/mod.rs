@@ -1,21 +1,46 @@
-use std::sync::Arc;
-use surrealdb::{
-    // engine::local::{Db, File},
-    engine::local::{Db, Mem},
-    Surreal,
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
+#[cfg(feature = "persistent-db")]
+use surrealdb::engine::local::File;
+#[cfg(not(feature = "persistent-db"))]
+use surrealdb::engine::local::Mem;
+use surrealdb::{engine::local::Db, Surreal};
 use tauri::async_runtime::Mutex;
 use tauri::{App, Manager};
 
+use crate::error::AppError;
+
+pub mod benchmark;
+pub mod conversation;
+pub mod library;
 pub mod logic;
+pub mod migration;
+pub mod prompt_template;
+pub mod stats;
 
 #[derive(Default)]
 pub struct Database {
     pub db: Arc<Mutex<Option<Surreal<Db>>>>,
 }
 
+/// Milliseconds since the unix epoch, used to timestamp the records
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
 impl Database {
-    pub fn init(app: &App) -> Result<(), String> {
+    /// The state is managed right away, without a connection when the database fails to open so
+    /// the `connect` command can retry and report the error
+    pub fn init(app: &App) -> Result<(), AppError> {
+        let database = Database::default();
+        let db = database.db.clone();
+        app.manage(database);
         let app_handle = app.app_handle();
         tauri::async_runtime::spawn(async move {
             let app_data_dir = app_handle
@@ -23,20 +48,58 @@ impl Database {
                 .app_data_dir()
                 .unwrap_or_default();
             tracing::info!("App data dir is: {}", app_data_dir.display());
-            // Initialize the database in the app data file
-            // let db = Surreal::new::<File>(format!("{}/db", app_data_dir.display()).as_str())
-            let db = Surreal::new::<Mem>(()).await.unwrap();
-            // Select a specific namespace / database
-            db.use_ns("my_ns")
-                .use_db("my_db")
-                .await
-                .map_err(|err| err.to_string())
-                .unwrap();
-            app_handle.manage(Database {
-                db: Arc::new(Mutex::new(Some(db))),
-            });
+            match Database::open(&app_data_dir).await {
+                Ok(opened) => *db.lock().await = Some(opened),
+                Err(err) => {
+                    tracing::error!("DB setup failed: {err}");
+                    return;
+                }
+            }
             tracing::info!("DB setup succesful");
+            if let Err(err) = library::start(&app_handle).await {
+                tracing::error!("Model library setup failed: {err}");
+            }
         });
         Ok(())
     }
+
+    /// Open the database in the app data dir, or in memory without the `persistent-db` feature,
+    /// and run the pending migrations
+    pub async fn open(app_data_dir: &Path) -> Result<Surreal<Db>, AppError> {
+        #[cfg(feature = "persistent-db")]
+        let db = {
+            std::fs::create_dir_all(app_data_dir)?;
+            let db_path = app_data_dir.join("db");
+            tracing::info!("Opening the database at: {}", db_path.display());
+            Surreal::new::<File>(db_path.display().to_string().as_str())
+                .await
+                .map_err(|err| match err.to_string().contains("lock file") {
+                    // RocksDB locks the database of the running app or pa-cli
+                    true => AppError::Db {
+                        reason: format!(
+                            "{} is used by another process, close the app or the other pa-cli first",
+                            db_path.display()
+                        ),
+                    },
+                    false => AppError::db(err),
+                })?
+        };
+        #[cfg(not(feature = "persistent-db"))]
+        let db = {
+            tracing::info!(
+                "Opening an in-memory database, {} is left untouched",
+                app_data_dir.display()
+            );
+            Surreal::new::<Mem>(())
+                .await
+                .map_err(AppError::db)?
+        };
+        // Select a specific namespace / database
+        db.use_ns("my_ns")
+            .use_db("my_db")
+            .await
+            .map_err(AppError::db)?;
+        migration::migrate(&db).await?;
+        Ok(db)
+    }
 }
@@ -0,0 +1,251 @@
+use surrealdb::{engine::local::Db, Surreal};
+use tauri::{Runtime, Window};
+
+use super::Database;
+use crate::error::AppError;
+
+pub use personal_assistant_types::{
+    PromptTemplate, DEFAULT_LAYOUT, DEFAULT_TEMPLATE, HISTORY_PLACEHOLDER, PROMPT_PLACEHOLDER,
+    SYSTEM_PLACEHOLDER,
+};
+
+fn preset(name: &str, system: &str, user: &str, assistant: &str, stop: &[&str]) -> PromptTemplate {
+    PromptTemplate {
+        name: name.to_owned(),
+        layout: DEFAULT_LAYOUT.to_owned(),
+        system: system.to_owned(),
+        user: user.to_owned(),
+        assistant: assistant.to_owned(),
+        stop: stop.iter().map(|stop| stop.to_string()).collect(),
+        builtin: true,
+    }
+}
+
+/// Built-in templates, listed before the stored ones
+pub fn presets() -> Vec<PromptTemplate> {
+    vec![
+        preset(
+            DEFAULT_TEMPLATE,
+            "A chat between a human (\"User\") and an AI assistant (\"Assistant\"). The assistant gives helpful, detailed, and polite answers to the human's questions.\nAssistant: How may I help you?\n",
+            "User: {{PROMPT}}\n",
+            "Assistant: {{PROMPT}}\n",
+            &["User:"],
+        ),
+        preset(
+            "Alpaca",
+            "Below is an instruction that describes a task. Write a response that appropriately completes the request.\n\n",
+            "### Instruction:\n{{PROMPT}}\n\n",
+            "### Response:\n{{PROMPT}}\n\n",
+            &["### Instruction:"],
+        ),
+        preset(
+            "Vicuna",
+            "A chat between a curious user and an artificial intelligence assistant. The assistant gives helpful, detailed, and polite answers to the user's questions.\n\n",
+            "USER: {{PROMPT}}\n",
+            "ASSISTANT: {{PROMPT}}</s>\n",
+            &["USER:"],
+        ),
+        preset(
+            "Llama-2-chat",
+            "<s>[INST] <<SYS>>\nYou are a helpful, respectful and honest assistant.\n<</SYS>>\n\n",
+            "{{PROMPT}} [/INST]",
+            " {{PROMPT}} </s><s>[INST] ",
+            &["[INST]"],
+        ),
+        preset(
+            "ChatML",
+            "<|im_start|>system\nYou are a helpful assistant.<|im_end|>\n",
+            "<|im_start|>user\n{{PROMPT}}<|im_end|>\n",
+            "<|im_start|>assistant\n{{PROMPT}}<|im_end|>\n",
+            &["<|im_end|>"],
+        ),
+        preset("Plain", "", "{{PROMPT}}", "{{PROMPT}}", &[]),
+    ]
+}
+
+/// Template used when neither the conversation nor the model config picked one
+pub fn default_template() -> PromptTemplate {
+    // The default template is the first preset
+    presets().swap_remove(0)
+}
+
+fn is_preset(name: &str) -> bool {
+    presets().iter().any(|preset| preset.name == name)
+}
+
+/// Presets followed by the stored templates
+pub async fn select_prompt_templates(db: &Surreal<Db>) -> Result<Vec<PromptTemplate>, AppError> {
+    let stored: Vec<PromptTemplate> = db
+        .select("prompt_template")
+        .await
+        .map_err(AppError::db)?;
+    let mut templates = presets();
+    templates.extend(stored);
+    Ok(templates)
+}
+
+pub async fn select_prompt_template(
+    db: &Surreal<Db>,
+    name: &str,
+) -> Result<PromptTemplate, AppError> {
+    if let Some(preset) = presets().into_iter().find(|preset| preset.name == name) {
+        return Ok(preset);
+    }
+    let template: Option<PromptTemplate> = db
+        .select(("prompt_template", name))
+        .await
+        .map_err(AppError::db)?;
+    template.ok_or_else(|| AppError::not_found(format!("Prompt template {name}")))
+}
+
+#[tauri::command]
+pub async fn list_prompt_templates(
+    state: tauri::State<'_, Database>,
+) -> Result<Vec<PromptTemplate>, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    select_prompt_templates(db).await
+}
+
+#[tauri::command]
+pub async fn add_prompt_template<R: Runtime>(
+    win: Window<R>,
+    mut template: PromptTemplate,
+    state: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    template.validate()?;
+    if is_preset(&template.name) {
+        return Err(AppError::already_exists(format!("Prompt template {}", template.name)));
+    }
+    template.builtin = false;
+    let created: Option<PromptTemplate> = db
+        .create(("prompt_template", template.name.as_str()))
+        .content(template)
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Prompt template added: {:#?}", created);
+    let created = created.ok_or_else(|| AppError::already_exists("Prompt template"))?;
+    let _ = win
+        .emit("template_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(format!("Prompt template added: {}", created.name))
+}
+
+#[tauri::command]
+pub async fn update_prompt_template<R: Runtime>(
+    win: Window<R>,
+    mut template: PromptTemplate,
+    state: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    template.validate()?;
+    if is_preset(&template.name) {
+        return Err(AppError::invalid_input(
+            "template name",
+            format!("{} is a built-in template", template.name),
+        ));
+    }
+    // Updating a missing record would create it
+    let existing: Option<PromptTemplate> = db
+        .select(("prompt_template", template.name.as_str()))
+        .await
+        .map_err(AppError::db)?;
+    if existing.is_none() {
+        return Err(AppError::not_found(format!("Prompt template {}", template.name)));
+    }
+    template.builtin = false;
+    let updated: Option<PromptTemplate> = db
+        .update(("prompt_template", template.name.as_str()))
+        .content(template)
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Prompt template updated: {:#?}", updated);
+    let updated = updated.ok_or_else(|| AppError::not_found("Prompt template"))?;
+    let _ = win
+        .emit("template_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(format!("Prompt template updated: {}", updated.name))
+}
+
+#[tauri::command]
+pub async fn delete_prompt_template<R: Runtime>(
+    win: Window<R>,
+    name: String,
+    state: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    if is_preset(&name) {
+        return Err(AppError::invalid_input(
+            "template name",
+            format!("{name} is a built-in template"),
+        ));
+    }
+    let deleted: Option<PromptTemplate> = db
+        .delete(("prompt_template", name.as_str()))
+        .await
+        .map_err(AppError::db)?;
+    tracing::info!("Prompt template deleted: {:#?}", deleted);
+    let deleted =
+        deleted.ok_or_else(|| AppError::not_found(format!("Prompt template {name}")))?;
+    let _ = win
+        .emit("template_sync_event", ())
+        .map_err(|err| err.to_string());
+    Ok(format!("Prompt template deleted: {}", deleted.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for preset in presets() {
+            assert!(preset.validate().is_ok(), "{} is not valid", preset.name);
+            assert!(preset.builtin);
+        }
+    }
+
+    #[test]
+    fn preset_names_are_unique() {
+        let presets = presets();
+        for (index, preset) in presets.iter().enumerate() {
+            assert!(presets[index + 1..].iter().all(|other| other.name != preset.name));
+        }
+        assert!(is_preset(DEFAULT_TEMPLATE));
+        assert_eq!(default_template().name, DEFAULT_TEMPLATE);
+    }
+
+    #[test]
+    fn templates_need_the_prompt_placeholder() {
+        let mut template = presets().remove(0);
+        template.user = "User: ".to_string();
+        assert!(matches!(
+            template.validate(),
+            Err(AppError::InvalidInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+use tauri::{AppHandle, Manager, Runtime};
+
+use super::{now_millis, Database};
+use crate::error::AppError;
+use crate::model::{InferenceRecord, Model};
+
+/// Store the stats of a prediction of the loaded model, a failure is only logged since the
+/// answer was already given
+pub async fn record_inference<R: Runtime>(app_handle: &AppHandle<R>, stats: &llm::InferenceStats) {
+    let record = match app_handle.state::<Model>().inference_record(stats, now_millis()) {
+        Ok(Some(record)) => record,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!("The inference stats were not recorded: {err}");
+            return;
+        }
+    };
+    let Some(state) = app_handle.try_state::<Database>() else {
+        tracing::warn!("The inference stats were not recorded: {}", AppError::DbNotConnected);
+        return;
+    };
+    if let Err(err) = insert_inference_record(&state, record).await {
+        tracing::warn!("The inference stats were not recorded: {err}");
+    }
+}
+
+async fn insert_inference_record(
+    state: &Database,
+    record: InferenceRecord,
+) -> Result<(), AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let created: Vec<InferenceRecord> = db
+        .create("inference_stats")
+        .content(record)
+        .await
+        .map_err(AppError::db)?;
+    tracing::debug!("Inference stats recorded: {:#?}", created);
+    Ok(())
+}
+
+/// Recorded predictions, oldest first, of the given model or of all the models
+#[tauri::command]
+pub async fn list_inference_records(
+    model_name: Option<String>,
+    state: tauri::State<'_, Database>,
+) -> Result<Vec<InferenceRecord>, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    let records: Vec<InferenceRecord> = match model_name {
+        Some(model_name) => db
+            .query("SELECT * FROM inference_stats WHERE model_name = $model_name ORDER BY timestamp ASC")
+            .bind(("model_name", model_name.as_str()))
+            .await
+            .map_err(AppError::db)?
+            .take(0)
+            .map_err(AppError::db)?,
+        None => db
+            .query("SELECT * FROM inference_stats ORDER BY timestamp ASC")
+            .await
+            .map_err(AppError::db)?
+            .take(0)
+            .map_err(AppError::db)?,
+    };
+    Ok(records)
+}
+
+/// Forget the recorded predictions of the given model or of all the models
+#[tauri::command]
+pub async fn clear_inference_records(
+    model_name: Option<String>,
+    state: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    // Get the database
+    let db = state.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
+    };
+    match model_name {
+        Some(model_name) => {
+            db.query("DELETE inference_stats WHERE model_name = $model_name")
+                .bind(("model_name", model_name.as_str()))
+                .await
+                .map_err(AppError::db)?
+                .check()
+                .map_err(AppError::db)?;
+        }
+        None => {
+            db
+                .query("DELETE inference_stats")
+                .await
+                .map_err(AppError::db)?
+                .check()
+                .map_err(AppError::db)?;
+        }
+    }
+    Ok(String::from("Inference stats cleared"))
+}
@@ -0,0 +1,4 @@
+//! The error of the commands is shared with the frontend, see `personal_assistant_types`. The
+//! errors of the database, the watcher and tauri are mapped with `AppError::db`, `AppError::io`
+//! and `AppError::internal`, the shared crate does not depend on them.
+pub use personal_assistant_types::AppError;
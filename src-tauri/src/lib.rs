@@ -0,0 +1,69 @@
+pub mod api;
+pub mod db;
+pub mod error;
+pub mod log;
+pub mod model;
+
+/// Build and run the desktop app
+pub fn run() {
+    tauri::Builder::default()
+        // .manage(model::Model::default())
+        .setup(|app| {
+            model::Model::init(app)?;
+            db::library::ModelLibrary::init(app)?;
+            db::Database::init(app)?;
+            api::ApiServer::init(app)?;
+            // log::setup_logger(app, log::LoggerOutput::Stdout, tracing::Level::INFO);
+            // log to ~/.config/ai.lbk.assistant/logs
+            log::setup_logger(app, log::LoggerOutput::default(), tracing::Level::INFO);
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            model::logic::load_model_config,
+            model::logic::load_dynamic_model,
+            model::logic::abort_model_load,
+            model::logic::unload_dynamic_model,
+            model::logic::inspect_model_file,
+            model::logic::inspect_lora_adapter,
+            model::logic::list_supported_architectures,
+            model::logic::predict,
+            model::logic::tokenize,
+            model::logic::count_tokens,
+            model::logic::stop_prediction,
+            model::logic::reset_session,
+            model::logic::drop_session,
+            model::logic::run_benchmark,
+            db::logic::connect,
+            db::logic::add_model_config,
+            db::logic::get_model_configs,
+            db::logic::delete_model_config,
+            db::logic::update_model_config,
+            db::logic::rename_model_config,
+            db::logic::set_model_config_template,
+            db::conversation::create_conversation,
+            db::conversation::list_conversations,
+            db::conversation::rename_conversation,
+            db::conversation::set_conversation_template,
+            db::conversation::delete_conversation,
+            db::conversation::load_conversation,
+            db::conversation::add_message,
+            db::library::list_model_directories,
+            db::library::add_model_directory,
+            db::library::delete_model_directory,
+            db::library::scan_model_library,
+            db::prompt_template::list_prompt_templates,
+            db::prompt_template::add_prompt_template,
+            db::prompt_template::update_prompt_template,
+            db::prompt_template::delete_prompt_template,
+            db::stats::list_inference_records,
+            db::stats::clear_inference_records,
+            db::benchmark::list_benchmarks,
+            db::benchmark::delete_benchmark,
+            db::benchmark::export_benchmarks,
+            api::logic::start_api_server,
+            api::logic::stop_api_server,
+            api::logic::api_server_status,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
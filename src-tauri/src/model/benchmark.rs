@@ -0,0 +1,232 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::AppError;
+
+use super::{BenchmarkConfig, BenchmarkResult, BenchmarkSample, Model, SamplingParameters};
+
+/// Texts of the benchmark suite, each one is repeated or cut to the prompt size
+pub const SUITE: &[&str] = &[
+    "Summarize the following notes for a colleague who missed the meeting. The team agreed to \
+     ship the release next week, the remaining bugs are in the settings page and the export, \
+     the documentation still needs screenshots and the installer must be tested on every platform.",
+    "Write a short story about a lighthouse keeper who finds a message in a bottle. The message \
+     is written in a language nobody in the village can read, and the keeper decides to travel \
+     to the city to find someone who can.",
+    "Explain to a beginner how a hash map works, why looking up a key is fast on average, what \
+     happens when two keys land in the same bucket and why the map grows when it gets too full.",
+    "Translate the meaning of this paragraph into plain words: the quarterly figures exceeded \
+     expectations owing to sustained demand across all regions, notwithstanding supply chain \
+     constraints that persisted throughout the period.",
+];
+/// Seed of the sampling, the runs generate the same tokens
+pub const SEED: u64 = 42;
+
+/// The first `count` words of `text` repeated as many times as needed
+fn words(text: &str, count: usize) -> String {
+    text.split_whitespace()
+        .cycle()
+        .take(count)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The longest repetition of `text` that does not go over `prompt_tokens` tokens
+pub fn sized_prompt(
+    text: &str,
+    prompt_tokens: usize,
+    tokenize: &mut dyn FnMut(&str) -> Result<usize, AppError>,
+) -> Result<String, AppError> {
+    // A word is at least one token, so `prompt_tokens` words are enough
+    let (mut fits, mut too_long) = (0, prompt_tokens + 1);
+    while too_long - fits > 1 {
+        let count = (fits + too_long) / 2;
+        match tokenize(&words(text, count))? <= prompt_tokens {
+            true => fits = count,
+            false => too_long = count,
+        }
+    }
+    Ok(words(text, fits))
+}
+
+/// Current resident memory of the process, read from `/proc`. The high-water mark is not used,
+/// it would keep the peak of a bigger model loaded earlier in the session.
+#[cfg(target_os = "linux")]
+pub fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// The memory is only measured on Linux
+#[cfg(not(target_os = "linux"))]
+pub fn resident_memory_bytes() -> Option<u64> {
+    None
+}
+
+/// Run the suite `config.repetitions` times against the loaded model, each prompt in a new
+/// session. `progress` is called with the done and total runs, the stop flag ends the benchmark.
+pub fn run(
+    model: &Model,
+    config: &BenchmarkConfig,
+    timestamp: i64,
+    stop_flag: &AtomicBool,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<BenchmarkResult, AppError> {
+    // The model config and load parameters of the results
+    let record = match model.inference_record(&Default::default(), timestamp)? {
+        Some(record) => record,
+        None => return Err(AppError::ModelNotLoaded),
+    };
+    config
+        .check(model.context_size())
+        .map_err(|reason| AppError::invalid_input("benchmark", reason))?;
+    let prompts = SUITE
+        .iter()
+        .map(|text| {
+            sized_prompt(text, config.prompt_tokens, &mut |prompt| {
+                Ok(model.tokenize(prompt)?.len())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let sampling = SamplingParameters {
+        maximum_token_count: Some(config.generation_tokens),
+        seed: Some(SEED),
+        ..Default::default()
+    };
+
+    let total = config.repetitions * prompts.len();
+    let mut samples = Vec::with_capacity(total);
+    // Sampled after each prediction, the session memory is freed once it ends
+    let mut peak_memory_bytes = resident_memory_bytes();
+    for repetition in 0..config.repetitions {
+        for (index, prompt) in prompts.iter().enumerate() {
+            if stop_flag.load(Ordering::SeqCst) {
+                return Err(AppError::BenchmarkStopped);
+            }
+            let stats = model.predict(None, prompt, None, &sampling, stop_flag, |_| {
+                llm::InferenceFeedback::Continue
+            })?;
+            samples.push(BenchmarkSample {
+                prompt: index,
+                repetition,
+                stats: (&stats).into(),
+            });
+            peak_memory_bytes = peak_memory_bytes.max(resident_memory_bytes());
+            progress(samples.len(), total);
+        }
+    }
+    // The last prediction may have been cut short by the stop flag
+    if stop_flag.load(Ordering::SeqCst) {
+        return Err(AppError::BenchmarkStopped);
+    }
+
+    let (prompt_eval, generation) = BenchmarkResult::throughputs(&samples, config.repetitions);
+    Ok(BenchmarkResult {
+        benchmark_id: format!("{timestamp:x}{:04x}", rand::random::<u16>()),
+        model_name: record.model_name,
+        model_architecture: record.model_architecture,
+        load_params: record.load_params,
+        threads: record.threads,
+        timestamp,
+        config: *config,
+        samples,
+        prompt_eval,
+        generation,
+        peak_memory_bytes,
+    })
+}
+
+/// One line per sample with the setup of its benchmark
+pub fn to_csv(results: &[BenchmarkResult]) -> String {
+    let mut csv = String::from(
+        "benchmark_id,timestamp,model_name,model_architecture,context_size,use_gpu,threads,\
+         prompt,repetition,prompt_tokens,feed_prompt_ms,prompt_tokens_per_second,predict_tokens,\
+         predict_ms,tokens_per_second,peak_memory_bytes\n",
+    );
+    for result in results {
+        for sample in &result.samples {
+            let stats = &sample.stats;
+            let model_name = match result.model_name.contains([',', '"']) {
+                true => format!("\"{}\"", result.model_name.replace('"', "\"\"")),
+                false => result.model_name.clone(),
+            };
+            csv.push_str(&format!(
+                "{},{},{model_name},{:?},{},{},{},{},{},{},{:.3},{:.3},{},{:.3},{:.3},{}\n",
+                result.benchmark_id,
+                result.timestamp,
+                result.model_architecture,
+                result.load_params.context_size,
+                result.load_params.use_gpu,
+                result.threads,
+                sample.prompt,
+                sample.repetition,
+                stats.prompt_tokens,
+                stats.feed_prompt_millis(),
+                stats.prompt_tokens_per_second(),
+                stats.predict_tokens,
+                stats.predict_duration.as_secs_f64() * 1000.0,
+                stats.tokens_per_second(),
+                result.peak_memory_bytes.map(|bytes| bytes.to_string()).unwrap_or_default(),
+            ));
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::mock_engine::{loaded_model, MockEngine};
+
+    #[test]
+    fn prompts_are_sized_in_tokens() {
+        // The mock tokenizer makes one token per word
+        let model = loaded_model("llama, q4");
+        let mut tokenize = |text: &str| -> Result<usize, AppError> { Ok(model.tokenize(text)?.len()) };
+        let prompt = sized_prompt(SUITE[0], 200, &mut tokenize).unwrap();
+        assert_eq!(prompt.split_whitespace().count(), 200);
+        let prompt = sized_prompt("one two three", 2, &mut tokenize).unwrap();
+        assert_eq!(prompt, "one two");
+    }
+
+    #[test]
+    fn benchmark_runs_the_suite_on_each_repetition() {
+        let model = loaded_model("llama, q4");
+        let config = BenchmarkConfig {
+            prompt_tokens: 16,
+            generation_tokens: 8,
+            repetitions: 2,
+        };
+        let mut done = Vec::new();
+        let result = run(&model, &config, 42, &AtomicBool::new(false), &mut |current, total| {
+            done.push((current, total))
+        })
+        .unwrap();
+        assert_eq!(result.samples.len(), 2 * SUITE.len());
+        assert_eq!(done.last(), Some(&(2 * SUITE.len(), 2 * SUITE.len())));
+        assert!(result.samples.iter().all(|sample| sample.stats.prompt_tokens == 16));
+        assert!(result.samples.iter().all(|sample| sample.stats.predict_tokens <= 8));
+        assert_eq!(result.peak_memory_bytes.is_some(), cfg!(target_os = "linux"));
+        let csv = to_csv(&[result]);
+        assert_eq!(csv.lines().count(), 1 + 2 * SUITE.len());
+        assert!(csv.lines().nth(1).unwrap().contains(",\"llama, q4\",Llama,"));
+    }
+
+    #[test]
+    fn benchmark_needs_a_loaded_model_and_stops() {
+        let model = Model::new(Box::<MockEngine>::default());
+        let config = BenchmarkConfig::default();
+        let err = run(&model, &config, 0, &AtomicBool::new(false), &mut |_, _| ()).unwrap_err();
+        assert!(matches!(err, AppError::ModelNotLoaded));
+        let model = loaded_model("llama, q4");
+        let err = run(&model, &config, 0, &AtomicBool::new(true), &mut |_, _| ()).unwrap_err();
+        assert!(matches!(err, AppError::BenchmarkStopped));
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::AtomicBool;
+
+use crate::error::AppError;
+
+use super::{llm_engine::LlmEngine, mock_engine::MockEngine, ModelConfig, SamplingParameters};
+
+pub use personal_assistant_types::LoadProgress;
+
+/// Environment variable selecting the engine at startup, `llm` (default) or `mock`
+pub const ENGINE_ENV: &str = "PA_INFERENCE_ENGINE";
+
+/// What to predict, see `Model::predict`
+pub struct PredictRequest<'a> {
+    /// The session of the conversation is kept for the next request
+    pub conversation_id: Option<&'a str>,
+    /// Full rendered template, fed to a new session
+    pub message: &'a str,
+    /// New user turn, fed to a kept session
+    pub turn: Option<&'a str>,
+    pub sampling: &'a SamplingParameters,
+    pub stop_flag: &'a AtomicBool,
+}
+
+/// Inference backend behind the `Model` state, the tauri commands only talk to this trait
+pub trait InferenceEngine: Send + Sync {
+    /// Load the model of the config, replacing the loaded one. Once `aborted` is set the progress
+    /// is no longer reported and the loaded model is dropped instead of replacing the current one.
+    fn load(
+        &self,
+        model_config: &ModelConfig,
+        model_params: llm::ModelParameters,
+        aborted: &AtomicBool,
+        progress: &mut dyn FnMut(LoadProgress),
+    ) -> Result<(), AppError>;
+
+    fn unload(&self) -> Result<(), AppError>;
+
+    fn is_loaded(&self) -> bool;
+
+    /// Threads an inference session runs on
+    fn threads(&self) -> usize;
+
+    /// Stream the inferred tokens to `on_token` until the end of text, the stop flag or a halt
+    fn predict(
+        &self,
+        request: PredictRequest<'_>,
+        on_token: &mut dyn FnMut(&str) -> llm::InferenceFeedback,
+    ) -> Result<llm::InferenceStats, AppError>;
+
+    fn tokenize(&self, text: &str) -> Result<Vec<llm::TokenId>, AppError>;
+
+    /// Start a new session for the conversation, the next prediction feeds the full template
+    fn reset_session(&self, conversation_id: &str) -> Result<(), AppError>;
+
+    /// Free the session of the conversation, returns whether there was one
+    fn drop_session(&self, conversation_id: &str) -> Result<bool, AppError>;
+}
+
+/// The mock engine is picked with the `mock-engine` feature or `PA_INFERENCE_ENGINE=mock`
+pub fn default_engine() -> Box<dyn InferenceEngine> {
+    let use_mock = cfg!(feature = "mock-engine")
+        || std::env::var(ENGINE_ENV).is_ok_and(|engine| engine.eq_ignore_ascii_case("mock"));
+    if use_mock {
+        tracing::warn!("Using the mock inference engine");
+        Box::<MockEngine>::default()
+    } else {
+        Box::<LlmEngine>::default()
+    }
+}
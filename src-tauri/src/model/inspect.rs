@@ -0,0 +1,565 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use super::ModelArchitecture;
+use crate::error::AppError;
+
+pub use personal_assistant_types::{LoraMetadata, ModelMetadata};
+
+const GGML_MAGIC: u32 = 0x6767_6d6c;
+const GGMF_MAGIC: u32 = 0x6767_6d66;
+const GGJT_MAGIC: u32 = 0x6767_6a74;
+const GGLA_MAGIC: u32 = 0x6767_6c61;
+const GGUF_MAGIC: u32 = 0x4655_4747;
+/// The file type is stored as `quantization_version * 1000 + file_type`
+const QUANTIZATION_VERSION_FACTOR: u32 = 1000;
+/// Bytes read after the magic, enough for the hyperparameters and the start of the vocabulary
+const SAMPLE_LEN: u64 = 64 * 1024;
+/// Tokens read to check that the hyperparameters were guessed right
+const CHECKED_TOKENS: usize = 16;
+/// A longer token means the vocabulary does not start where the guessed hyperparameters end
+const MAX_TOKEN_LEN: usize = 1024;
+/// The tensor data of a LoRA adapter starts on a multiple of this offset
+const TENSOR_ALIGNMENT: u64 = 32;
+/// A longer tensor name means the file is corrupted
+const MAX_TENSOR_NAME_LEN: usize = 1024;
+
+/// Hyperparameters in the order an architecture writes them
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Vocabulary,
+    Context,
+    Embedding,
+    Mult,
+    Heads,
+    Layers,
+    Rotary,
+    ParallelResidual,
+    /// A float, e.g. the ALiBi bias of MPT
+    Float,
+    FileType,
+}
+
+/// Layouts tried in order, the first one whose values make sense wins. This is a guess, the
+/// files do not store their architecture.
+const LAYOUTS: &[(ModelArchitecture, &[Field])] = {
+    use Field::*;
+    &[
+        (
+            ModelArchitecture::Llama,
+            &[Vocabulary, Embedding, Mult, Heads, Layers, Rotary, FileType],
+        ),
+        (
+            ModelArchitecture::GptJ,
+            &[Vocabulary, Context, Embedding, Heads, Layers, Rotary, FileType],
+        ),
+        (
+            ModelArchitecture::GptNeoX,
+            &[Vocabulary, Context, Embedding, Heads, Layers, Rotary, ParallelResidual, FileType],
+        ),
+        (
+            ModelArchitecture::Mpt,
+            &[Embedding, Context, Heads, Layers, Vocabulary, Float, Float, FileType],
+        ),
+        (
+            ModelArchitecture::Gpt2,
+            &[Vocabulary, Context, Embedding, Heads, Layers, FileType],
+        ),
+        (
+            ModelArchitecture::Bloom,
+            &[Vocabulary, Embedding, Mult, Heads, Layers, FileType],
+        ),
+    ]
+};
+
+fn file_type_name(file_type: u32) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        4 => "Q4_1_SOME_F16",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        _ => return None,
+    })
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Check the first tokens of the vocabulary, a token is its length, its bytes and, in the
+/// versioned containers, its score
+fn vocabulary_is_valid(mut bytes: &[u8], vocabulary_size: usize, scored: bool) -> bool {
+    for _ in 0..vocabulary_size.min(CHECKED_TOKENS) {
+        let Ok(len) = read_u32(&mut bytes) else {
+            // The sample ends, nothing contradicts the layout
+            return true;
+        };
+        let len = len as usize;
+        if len > MAX_TOKEN_LEN {
+            return false;
+        }
+        let token_len = if scored { len + 4 } else { len };
+        if bytes.len() < token_len {
+            return true;
+        }
+        if scored {
+            let score = f32::from_le_bytes([bytes[len], bytes[len + 1], bytes[len + 2], bytes[len + 3]]);
+            if !score.is_finite() {
+                return false;
+            }
+        }
+        bytes = &bytes[token_len..];
+    }
+    true
+}
+
+struct Hyperparameters {
+    vocabulary_size: usize,
+    embedding_size: usize,
+    layer_count: usize,
+    head_count: usize,
+    context_size: Option<usize>,
+    file_type: u32,
+}
+
+/// Read the hyperparameters with the layout of an architecture, `None` if they make no sense
+fn match_layout(
+    architecture: ModelArchitecture,
+    layout: &[Field],
+    sample: &[u8],
+    scored: bool,
+) -> Option<Hyperparameters> {
+    let values: Vec<(Field, u32)> = layout
+        .iter()
+        .zip(sample.get(..layout.len() * 4)?.chunks_exact(4))
+        .map(|(field, bytes)| (*field, u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])))
+        .collect();
+    let get = |field: Field| {
+        values
+            .iter()
+            .find(|(candidate, _)| *candidate == field)
+            .map(|(_, value)| *value)
+    };
+    let vocabulary_size = get(Field::Vocabulary)? as usize;
+    let embedding_size = get(Field::Embedding)? as usize;
+    let layer_count = get(Field::Layers)? as usize;
+    let head_count = get(Field::Heads)? as usize;
+    let context_size = get(Field::Context).map(|context| context as usize);
+    let file_type = get(Field::FileType)?;
+    let plausible = (1..=1_000_000).contains(&vocabulary_size)
+        && (1..=1_000).contains(&layer_count)
+        && head_count > 0
+        && embedding_size % head_count == 0
+        && context_size.map_or(true, |context| (1..=1_000_000).contains(&context))
+        && get(Field::ParallelResidual).map_or(true, |parallel| parallel <= 1)
+        && file_type_name(file_type % QUANTIZATION_VERSION_FACTOR).is_some()
+        && match (architecture, get(Field::Rotary)) {
+            // LLaMA rotates the whole head
+            (ModelArchitecture::Llama, Some(rotary)) => {
+                rotary as usize == embedding_size / head_count
+            }
+            // Rotary dimensions come in pairs
+            (_, Some(rotary)) => {
+                rotary >= 8 && rotary % 2 == 0 && rotary as usize <= embedding_size / head_count
+            }
+            (_, None) => true,
+        };
+    (plausible && vocabulary_is_valid(&sample[layout.len() * 4..], vocabulary_size, scored))
+        .then_some(Hyperparameters {
+            vocabulary_size,
+            embedding_size,
+            layer_count,
+            head_count,
+            context_size,
+            file_type,
+        })
+}
+
+/// Read the header of a GGML model, the error is the reason why the file can not be loaded
+fn read_header(reader: &mut impl Read) -> Result<ModelMetadata, String> {
+    let truncated = |_| "the file is too short to be a model".to_string();
+    let magic = read_u32(reader).map_err(truncated)?;
+    let (container, version) = match magic {
+        GGML_MAGIC => ("GGML", 1),
+        GGMF_MAGIC => ("GGMF", read_u32(reader).map_err(truncated)?),
+        GGJT_MAGIC => ("GGJT", read_u32(reader).map_err(truncated)?),
+        GGUF_MAGIC => {
+            return Err(
+                "GGUF files are not supported yet, use a GGML (GGJT) conversion of the model"
+                    .to_string(),
+            )
+        }
+        GGLA_MAGIC => {
+            return Err("this is a LoRA adapter, it is applied on top of a model".to_string())
+        }
+        _ => return Err(format!("unknown magic {magic:#010x}, this is not a GGML model")),
+    };
+    match (container, version) {
+        ("GGML", _) | ("GGMF", 1) | ("GGJT", 1..=3) => (),
+        _ => return Err(format!("{container} version {version} is not supported")),
+    }
+
+    let mut sample = Vec::new();
+    reader
+        .take(SAMPLE_LEN)
+        .read_to_end(&mut sample)
+        .map_err(|err| err.to_string())?;
+    // Only the unversioned container has no token scores
+    let scored = container != "GGML";
+    let (architecture, hyperparameters) = LAYOUTS
+        .iter()
+        .find_map(|(architecture, layout)| {
+            match_layout(*architecture, layout, &sample, scored)
+                .map(|hyperparameters| (*architecture, hyperparameters))
+        })
+        .ok_or_else(|| "the hyperparameters do not match any supported architecture".to_string())?;
+    let file_type = hyperparameters.file_type;
+    Ok(ModelMetadata {
+        container: container.to_string(),
+        version,
+        file_type: file_type_name(file_type % QUANTIZATION_VERSION_FACTOR)
+            .unwrap_or_default()
+            .to_string(),
+        quantization_version: file_type / QUANTIZATION_VERSION_FACTOR,
+        vocabulary_size: hyperparameters.vocabulary_size,
+        embedding_size: hyperparameters.embedding_size,
+        layer_count: hyperparameters.layer_count,
+        head_count: hyperparameters.head_count,
+        context_size: hyperparameters.context_size,
+        architecture,
+    })
+}
+
+/// Read the header of the model file at `path` without loading the weights
+pub fn inspect(path: &Path) -> Result<ModelMetadata, AppError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_header(&mut reader).map_err(|reason| AppError::UnsupportedModel {
+        path: path.to_owned(),
+        reason,
+    })
+}
+
+/// Layer of a tensor, from names like `layers.0.attention.wq.weight.loraA`
+fn tensor_layer(name: &str) -> Option<usize> {
+    name.split('.').find_map(|part| part.parse().ok())
+}
+
+/// Read the header and the tensor descriptions of the LoRA adapter at `path`, the data is
+/// skipped
+fn read_lora(reader: &mut (impl Read + Seek), path: &Path) -> Result<LoraMetadata, AppError> {
+    let unsupported = |reason: String| AppError::UnsupportedModel {
+        path: path.to_owned(),
+        reason,
+    };
+    let truncated = |_| unsupported("the file is too short to be a LoRA adapter".to_string());
+    match read_u32(reader).map_err(truncated)? {
+        GGLA_MAGIC => (),
+        GGML_MAGIC | GGMF_MAGIC | GGJT_MAGIC | GGUF_MAGIC => {
+            return Err(unsupported("this is a model, not a LoRA adapter".to_string()))
+        }
+        magic => {
+            return Err(unsupported(format!(
+                "unknown magic {magic:#010x}, this is not a LoRA adapter"
+            )))
+        }
+    }
+    let version = read_u32(reader).map_err(truncated)?;
+    if version != 1 {
+        return Err(unsupported(format!("LoRA adapter version {version} is not supported")));
+    }
+    let rank = read_u32(reader).map_err(truncated)?;
+    let alpha = read_u32(reader).map_err(truncated)?;
+
+    let mut metadata = LoraMetadata {
+        version,
+        rank,
+        alpha,
+        tensor_count: 0,
+        layer_count: 0,
+        dimensions: Vec::new(),
+    };
+    let corrupted = || unsupported("the tensors of the LoRA adapter are corrupted".to_string());
+    let truncated_tensor =
+        |_| unsupported("the LoRA adapter ends in the middle of a tensor".to_string());
+    let start = reader.stream_position()?;
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(start))?;
+    // The tensors run until the end of the file
+    while reader.stream_position()? < file_len {
+        let dimension_count = read_u32(reader).map_err(truncated_tensor)?;
+        let name_len = read_u32(reader).map_err(truncated_tensor)? as usize;
+        let file_type = read_u32(reader).map_err(truncated_tensor)?;
+        if !(1..=2).contains(&dimension_count) || name_len > MAX_TENSOR_NAME_LEN {
+            return Err(corrupted());
+        }
+        let dimensions = (0..dimension_count)
+            .map(|_| read_u32(reader))
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(truncated_tensor)?;
+        let mut name = vec![0; name_len];
+        reader.read_exact(&mut name).map_err(truncated_tensor)?;
+        let name = String::from_utf8_lossy(&name);
+        for &dimension in &dimensions {
+            if !metadata.dimensions.contains(&(dimension as usize)) {
+                metadata.dimensions.push(dimension as usize);
+            }
+        }
+        if let Some(layer) = tensor_layer(&name) {
+            metadata.layer_count = metadata.layer_count.max(layer + 1);
+        }
+        metadata.tensor_count += 1;
+        let element_size: u64 = match file_type {
+            0 => 4,
+            1 => 2,
+            _ => {
+                return Err(unsupported(format!(
+                    "tensor {name} is quantized, only F32 and F16 adapters are supported"
+                )))
+            }
+        };
+        let too_large = || {
+            AppError::invalid_input(
+                format!("LoRA tensor {name}"),
+                format!("its dimensions {dimensions:?} are too large"),
+            )
+        };
+        let data_len = dimensions
+            .iter()
+            .try_fold(element_size, |len, &dimension| len.checked_mul(u64::from(dimension)))
+            .ok_or_else(too_large)?;
+        let offset = reader.stream_position()?;
+        let padding = (TENSOR_ALIGNMENT - offset % TENSOR_ALIGNMENT) % TENSOR_ALIGNMENT;
+        let skipped = padding
+            .checked_add(data_len)
+            .and_then(|skipped| i64::try_from(skipped).ok())
+            .ok_or_else(too_large)?;
+        // Seeking past the end succeeds, the data of the tensor is missing then
+        if reader.seek(SeekFrom::Current(skipped))? > file_len {
+            return Err(unsupported(format!("the data of tensor {name} is truncated")));
+        }
+    }
+    if metadata.tensor_count == 0 {
+        return Err(unsupported("the LoRA adapter has no tensors".to_string()));
+    }
+    metadata.dimensions.sort_unstable();
+    Ok(metadata)
+}
+
+/// Read a LoRA adapter without applying it
+pub fn inspect_lora(path: &Path) -> Result<LoraMetadata, AppError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_lora(&mut reader, path)
+}
+
+/// Check that the adapters exist and fit the model before loading it. The model header is only
+/// a guess, when it can not be read llm is left to report the problems.
+pub fn validate_lora_adapters(model_path: &Path, adapters: &[PathBuf]) -> Result<(), AppError> {
+    let model = inspect(model_path).ok();
+    for (index, adapter) in adapters.iter().enumerate() {
+        let failed = |reason: String| AppError::LoadFailed {
+            path: adapter.clone(),
+            reason,
+        };
+        if adapters[..index].contains(adapter) {
+            return Err(failed("the LoRA adapter is applied twice".to_string()));
+        }
+        if !adapter.is_file() {
+            return Err(failed("the LoRA adapter is missing, it was moved or deleted".to_string()));
+        }
+        let lora = inspect_lora(adapter)?;
+        if let Some(reason) = model.as_ref().and_then(|model| lora.incompatibility(model)) {
+            return Err(failed(format!("the LoRA adapter does not fit the model, {reason}")));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header of a model followed by a few tokens
+    fn model_file(magic: u32, version: Option<u32>, hyperparameters: &[u32]) -> Vec<u8> {
+        let mut bytes = magic.to_le_bytes().to_vec();
+        bytes.extend(version.iter().flat_map(|version| version.to_le_bytes()));
+        bytes.extend(hyperparameters.iter().flat_map(|value| value.to_le_bytes()));
+        for token in ["<unk>", "<s>", "</s>", "!", "hello"] {
+            bytes.extend((token.len() as u32).to_le_bytes());
+            bytes.extend(token.as_bytes());
+            if magic != GGML_MAGIC {
+                bytes.extend(0.5_f32.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn read(bytes: Vec<u8>) -> Result<ModelMetadata, String> {
+        read_header(&mut bytes.as_slice())
+    }
+
+    #[test]
+    fn reads_a_llama_header() {
+        let metadata = read(model_file(GGJT_MAGIC, Some(3), &[32000, 4096, 256, 32, 32, 128, 2002])).unwrap();
+        assert_eq!(
+            metadata,
+            ModelMetadata {
+                container: "GGJT".to_string(),
+                version: 3,
+                file_type: "Q4_0".to_string(),
+                quantization_version: 2,
+                vocabulary_size: 32000,
+                embedding_size: 4096,
+                layer_count: 32,
+                head_count: 32,
+                context_size: None,
+                architecture: ModelArchitecture::Llama,
+            }
+        );
+    }
+
+    #[test]
+    fn guesses_the_architecture_from_the_hyperparameters() {
+        let gpt_j = read(model_file(GGJT_MAGIC, Some(1), &[50400, 2048, 4096, 16, 28, 64, 1])).unwrap();
+        assert_eq!(gpt_j.architecture, ModelArchitecture::GptJ);
+        assert_eq!(gpt_j.context_size, Some(2048));
+        let gpt_neox =
+            read(model_file(GGJT_MAGIC, Some(3), &[50432, 2048, 2560, 32, 32, 20, 1, 2008])).unwrap();
+        assert_eq!(gpt_neox.architecture, ModelArchitecture::GptNeoX);
+        assert_eq!(gpt_neox.file_type, "Q5_0");
+        let gpt_2 = read(model_file(GGML_MAGIC, None, &[50257, 1024, 768, 12, 12, 1])).unwrap();
+        assert_eq!(gpt_2.architecture, ModelArchitecture::Gpt2);
+        assert_eq!(gpt_2.container, "GGML");
+    }
+
+    #[test]
+    fn rejects_gguf_files() {
+        let err = read(model_file(GGUF_MAGIC, Some(2), &[])).unwrap_err();
+        assert!(err.contains("GGUF"), "{err}");
+    }
+
+    #[test]
+    fn rejects_lora_adapters_and_unknown_files() {
+        assert!(read(model_file(GGLA_MAGIC, Some(1), &[])).unwrap_err().contains("LoRA"));
+        assert!(read(b"PK\x03\x04 not a model".to_vec()).unwrap_err().contains("magic"));
+        assert!(read(b"gg".to_vec()).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        let err = read(model_file(GGJT_MAGIC, Some(4), &[32000, 4096, 256, 32, 32, 128, 2])).unwrap_err();
+        assert_eq!(err, "GGJT version 4 is not supported");
+    }
+
+    #[test]
+    fn rejects_nonsense_hyperparameters() {
+        let err = read(model_file(GGJT_MAGIC, Some(3), &[0, 0, 0, 0, 0, 0, 0])).unwrap_err();
+        assert!(err.contains("hyperparameters"), "{err}");
+    }
+
+    /// LoRA adapter patching the query of the given layers with F32 tensors
+    fn lora_file(embedding_size: u32, rank: u32, layers: &[u32]) -> Vec<u8> {
+        let mut bytes = [GGLA_MAGIC, 1, rank, rank * 2]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        for layer in layers {
+            for (suffix, dimensions) in [("loraA", [embedding_size, rank]), ("loraB", [rank, embedding_size])] {
+                let name = format!("layers.{layer}.attention.wq.weight.{suffix}");
+                for value in [2, name.len() as u32, 0, dimensions[0], dimensions[1]] {
+                    bytes.extend(value.to_le_bytes());
+                }
+                bytes.extend(name.as_bytes());
+                let padding = (TENSOR_ALIGNMENT as usize - bytes.len() % TENSOR_ALIGNMENT as usize)
+                    % TENSOR_ALIGNMENT as usize;
+                bytes.extend(vec![0; padding + (embedding_size * rank * 4) as usize]);
+            }
+        }
+        bytes
+    }
+
+    fn read_adapter(bytes: Vec<u8>) -> Result<LoraMetadata, AppError> {
+        read_lora(&mut io::Cursor::new(bytes), Path::new("adapter.bin"))
+    }
+
+    #[test]
+    fn reads_a_lora_adapter() {
+        let lora = read_adapter(lora_file(64, 8, &[0, 1, 2])).unwrap();
+        assert_eq!(
+            lora,
+            LoraMetadata {
+                version: 1,
+                rank: 8,
+                alpha: 16,
+                tensor_count: 6,
+                layer_count: 3,
+                dimensions: vec![8, 64],
+            }
+        );
+        assert!(read_adapter(model_file(GGJT_MAGIC, Some(3), &[32000, 4096, 256, 32, 32, 128, 2]))
+            .unwrap_err()
+            .to_string()
+            .contains("not a LoRA adapter"));
+        assert!(read_adapter(lora_file(64, 8, &[])).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_adapters() {
+        let mut bytes = lora_file(64, 8, &[0]);
+        bytes.extend([2, 0, 0, 0, 5, 0]);
+        let err = read_adapter(bytes).unwrap_err();
+        assert!(err.to_string().contains("middle of a tensor"), "{err}");
+        let mut bytes = lora_file(64, 8, &[0]);
+        bytes.truncate(bytes.len() - 4);
+        let err = read_adapter(bytes).unwrap_err();
+        assert!(err.to_string().contains("layers.0.attention.wq.weight.loraB"), "{err}");
+    }
+
+    #[test]
+    fn rejects_oversized_tensor_dimensions() {
+        let mut bytes = [GGLA_MAGIC, 1, 8, 16]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        let name = "layers.0.attention.wq.weight.loraA";
+        for value in [2, name.len() as u32, 0, u32::MAX, u32::MAX] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(name.as_bytes());
+        let err = read_adapter(bytes).unwrap_err();
+        assert!(matches!(&err, AppError::InvalidInput { field, .. } if field.contains(name)), "{err:?}");
+    }
+
+    #[test]
+    fn checks_that_the_adapter_fits_the_model() {
+        let model = read(model_file(GGJT_MAGIC, Some(3), &[32000, 4096, 256, 32, 32, 128, 2002])).unwrap();
+        let lora = |embedding_size, layers: &[u32]| LoraMetadata {
+            version: 1,
+            rank: 8,
+            alpha: 16,
+            tensor_count: layers.len() * 2,
+            layer_count: layers.iter().max().map_or(0, |layer| *layer as usize + 1),
+            dimensions: vec![8, embedding_size],
+        };
+        assert_eq!(lora(4096, &[0, 31]).incompatibility(&model), None);
+        assert!(lora(5120, &[0]).incompatibility(&model).unwrap().contains("model size"));
+        assert!(lora(4096, &[39]).incompatibility(&model).unwrap().contains("40 layers"));
+    }
+}
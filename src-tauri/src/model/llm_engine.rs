@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::error::AppError;
+
+use super::{
+    engine::{InferenceEngine, LoadProgress, PredictRequest},
+    inspect, ModelConfig, ModelParameters, SamplingParameters, DEFAULT_TRAINED_CONTEXT,
+};
+
+/// Maximum number of chat sessions kept alive, each one holds its own KV cache
+pub const MAX_SESSIONS: usize = 4;
+
+/// A seeded RNG makes the answer reproducible
+fn rng(sampling: &SamplingParameters) -> StdRng {
+    match sampling.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// An inference session kept alive between the messages of a conversation
+struct ChatSession {
+    session: llm::InferenceSession,
+    /// Whether the full template was already fed to the session
+    primed: bool,
+    last_used: Instant,
+}
+
+impl ChatSession {
+    fn new(model: &dyn llm::Model) -> Self {
+        Self {
+            session: model.start_session(Default::default()),
+            primed: false,
+            last_used: Instant::now(),
+        }
+    }
+}
+
+/// Engine backed by the `llm` crate
+#[derive(Default)]
+pub struct LlmEngine {
+    // Only locked to swap the model, a prediction holds its own handle
+    model: Mutex<Option<Arc<dyn llm::Model>>>,
+    // Whether a model is loaded, read without locking the model
+    loaded: AtomicBool,
+    // Chat sessions, one per conversation id
+    sessions: Mutex<HashMap<String, ChatSession>>,
+}
+
+impl LlmEngine {
+    /// Handle on the loaded model, the lock is released before it is used
+    fn loaded_model(&self) -> Result<Arc<dyn llm::Model>, AppError> {
+        match self.model.lock()?.as_ref() {
+            Some(model) => Ok(model.clone()),
+            None => Err(AppError::ModelNotLoaded),
+        }
+    }
+
+    /// Whether `model` is still the loaded model, a session of a replaced model is not kept
+    fn is_current(&self, model: &Arc<dyn llm::Model>) -> Result<bool, AppError> {
+        let address = Arc::as_ptr(model).cast::<()>();
+        Ok(self
+            .model
+            .lock()?
+            .as_ref()
+            .is_some_and(|current| Arc::as_ptr(current).cast::<()>() == address))
+    }
+
+    /// Put back a chat session and evict the least recently used ones
+    fn store_session(&self, conversation_id: String, mut chat_session: ChatSession) -> Result<(), AppError> {
+        let mut sessions = self.sessions.lock()?;
+        chat_session.last_used = Instant::now();
+        sessions.insert(conversation_id, chat_session);
+        while sessions.len() > MAX_SESSIONS {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, chat_session)| chat_session.last_used)
+                .map(|(conversation_id, _)| conversation_id.clone());
+            if let Some(oldest) = oldest {
+                tracing::info!("Evicting the session of conversation {oldest}");
+                sessions.remove(&oldest);
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop all the chat sessions, they are bound to the loaded model
+    fn clear_sessions(&self) -> Result<(), AppError> {
+        self.sessions.lock()?.clear();
+        Ok(())
+    }
+}
+
+impl InferenceEngine for LlmEngine {
+    fn load(
+        &self,
+        model_config: &ModelConfig,
+        model_params: llm::ModelParameters,
+        aborted: &AtomicBool,
+        progress: &mut dyn FnMut(LoadProgress),
+    ) -> Result<(), AppError> {
+        tracing::info!("Got model_config: {:#?}", model_config);
+        tracing::info!("Got model_params: {:#?}", model_params);
+        if !model_config.model_path.is_file() {
+            return Err(AppError::LoadFailed {
+                path: model_config.model_path.clone(),
+                reason: "the file is missing, it was moved or deleted".to_string(),
+            });
+        }
+        let shared_params = ModelParameters::from(&model_params);
+        if let Some(rope_overrides) = &shared_params.rope_overrides {
+            rope_overrides
+                .check()
+                .map_err(|reason| AppError::invalid_input("rope_overrides", reason))?;
+        }
+        let trained_context = inspect::inspect(&model_config.model_path)
+            .ok()
+            .and_then(|metadata| metadata.context_size)
+            .unwrap_or(DEFAULT_TRAINED_CONTEXT);
+        for warning in shared_params.rope_warnings(trained_context) {
+            tracing::warn!("{warning}");
+        }
+        if let Some(lora_adapters) = &model_params.lora_adapters {
+            inspect::validate_lora_adapters(&model_config.model_path, lora_adapters)?;
+        }
+
+        let architecture = model_config.model_architecture.to_llm().ok_or_else(|| {
+            let name = format!("{:?}", model_config.model_architecture);
+            AppError::UnsupportedModel {
+                path: model_config.model_path.clone(),
+                reason: format!(
+                    "{name} support was not built, enable the `{}` feature",
+                    name.to_lowercase()
+                ),
+            }
+        })?;
+        let model = llm::load_dynamic(
+            Some(architecture),
+            &model_config.model_path,
+            model_config.tokenizer_source.clone().into(),
+            model_params,
+            |load_progress| {
+                if aborted.load(Ordering::SeqCst) {
+                    return;
+                }
+                progress(match load_progress {
+                    llm::LoadProgress::HyperparametersLoaded => LoadProgress::HyperparametersLoaded,
+                    llm::LoadProgress::ContextSize { bytes } => LoadProgress::ContextSize { bytes },
+                    llm::LoadProgress::LoraApplied { name, .. } => LoadProgress::LoraApplied {
+                        name: name.to_owned(),
+                    },
+                    llm::LoadProgress::TensorLoaded {
+                        current_tensor,
+                        tensor_count,
+                    } => LoadProgress::TensorLoaded {
+                        current_tensor,
+                        tensor_count,
+                    },
+                    llm::LoadProgress::Loaded {
+                        file_size,
+                        tensor_count,
+                    } => LoadProgress::Loaded {
+                        bytes_read: file_size,
+                        tensor_count,
+                    },
+                })
+            },
+        )
+        .map_err(|err| AppError::LoadFailed {
+            path: model_config.model_path.clone(),
+            reason: err.to_string(),
+        })?;
+        // llm can not be interrupted, the model is only dropped once loaded
+        if aborted.load(Ordering::SeqCst) {
+            return Err(AppError::LoadAborted);
+        }
+        self.clear_sessions()?;
+        *self.model.lock()? = Some(Arc::from(model));
+        self.loaded.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn unload(&self) -> Result<(), AppError> {
+        self.clear_sessions()?;
+        *self.model.lock()? = None;
+        self.loaded.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.loaded.load(Ordering::SeqCst)
+    }
+
+    fn threads(&self) -> usize {
+        llm::InferenceSessionConfig::default().n_threads
+    }
+
+    fn predict(
+        &self,
+        request: PredictRequest<'_>,
+        on_token: &mut dyn FnMut(&str) -> llm::InferenceFeedback,
+    ) -> Result<llm::InferenceStats, AppError> {
+        let model = self.loaded_model()?;
+        // Reuse the session of the conversation if it is still alive
+        let taken_session = match request.conversation_id {
+            Some(conversation_id) => self
+                .sessions
+                .lock()?
+                .remove(conversation_id),
+            None => None,
+        };
+        let mut chat_session =
+            taken_session.unwrap_or_else(|| ChatSession::new(model.as_ref()));
+        let prompt = match (chat_session.primed, request.turn) {
+            (true, Some(turn)) => turn,
+            _ => request.message,
+        };
+
+        let res = chat_session.session.infer::<Infallible>(
+            model.as_ref(),
+            &mut rng(request.sampling),
+            &llm::InferenceRequest {
+                prompt: prompt.into(),
+                parameters: &request.sampling.inference_parameters(),
+                play_back_previous_tokens: false,
+                maximum_token_count: request.sampling.maximum_token_count,
+            },
+            // OutputRequest
+            &mut Default::default(),
+            |r| match r {
+                // The user asked to stop the prediction
+                _ if request.stop_flag.load(Ordering::SeqCst) => {
+                    tracing::info!("Prediction stopped by the user");
+                    Ok(llm::InferenceFeedback::Halt)
+                }
+                llm::InferenceResponse::InferredToken(t) => Ok(on_token(t.as_str())),
+                llm::InferenceResponse::EotToken => Ok(llm::InferenceFeedback::Halt),
+                _ => Ok(llm::InferenceFeedback::Continue),
+            },
+        );
+
+        match res {
+            Ok(result) => {
+                tracing::debug!("\n{result}");
+                // The model may have been unloaded or replaced during the prediction
+                if let (Some(conversation_id), true) =
+                    (request.conversation_id, self.is_current(&model)?)
+                {
+                    chat_session.primed = true;
+                    self.store_session(conversation_id.to_owned(), chat_session)?;
+                }
+                Ok(result)
+            }
+            Err(err) => {
+                // The session is left in an unknown state (e.g. context full), it is dropped
+                tracing::error!("\n{err}");
+                Err(err.into())
+            }
+        }
+    }
+
+    fn tokenize(&self, text: &str) -> Result<Vec<llm::TokenId>, AppError> {
+        Ok(self
+            .loaded_model()?
+            .tokenizer()
+            .tokenize(text, false)?
+            .into_iter()
+            .map(|(_, token_id)| token_id)
+            .collect())
+    }
+
+    fn reset_session(&self, conversation_id: &str) -> Result<(), AppError> {
+        let model = self.loaded_model()?;
+        self.store_session(conversation_id.to_owned(), ChatSession::new(model.as_ref()))
+    }
+
+    fn drop_session(&self, conversation_id: &str) -> Result<bool, AppError> {
+        Ok(self
+            .sessions
+            .lock()?
+            .remove(conversation_id)
+            .is_some())
+    }
+}
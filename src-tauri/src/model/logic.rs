@@ -1,106 +1,247 @@
-use std::{convert::Infallible, io::Write};
-use tauri::{Runtime, Window};
+use std::{path::PathBuf, sync::atomic::Ordering};
+use futures::future::{self, Either};
+use tauri::{Manager, Runtime, Window};
 
-use super::{Model, ModelConfig, ModelParametersWrapper};
+use crate::{
+    db::{
+        benchmark::insert_benchmark, logic::record_lora_adapters, now_millis,
+        prompt_template::PromptTemplate, stats::record_inference, Database,
+    },
+    error::AppError,
+};
 
+use super::{
+    benchmark,
+    inspect::{self, LoraMetadata, ModelMetadata},
+    template::ChatTurn, BenchmarkConfig, BenchmarkProgress, BenchmarkResult, Model, ModelArchitecture,
+    ModelConfig, ModelParams, SamplingParameters, TokenCount,
+};
+
+/// Answer the `prompt`, the `history` holds the previous messages of the conversation and is only
+/// rendered when the conversation has no session yet
 #[tauri::command]
 pub async fn predict<R: Runtime>(
     win: Window<R>,
-    message: &str,
+    conversation_id: String,
+    history: Vec<ChatTurn>,
+    prompt: String,
+    template: PromptTemplate,
+    sampling: SamplingParameters,
     state: tauri::State<'_, Model>,
-) -> Result<llm::InferenceStats, String> {
-    tracing::debug!("Predict {message:#?}");
-    let model_guard = state.model.lock().map_err(|err| err.to_string())?;
-    let model = match model_guard.as_ref() {
-        Some(model) => model,
-        None => return Err("Model not found".to_string()),
-    };
-    let mut session = model.start_session(Default::default());
-
-    let res = session.infer::<Infallible>(
-        model.as_ref(),
-        &mut rand::thread_rng(),
-        &llm::InferenceRequest {
-            prompt: message.into(),
-            parameters: &llm::InferenceParameters::default(),
-            play_back_previous_tokens: false,
-            maximum_token_count: None,
-        },
-        // OutputRequest
-        &mut Default::default(),
-        |r| match r {
-            llm::InferenceResponse::PromptToken(t) => {
-                print!("{t}");
-                std::io::stdout().flush().unwrap();
-                Ok(llm::InferenceFeedback::Continue)
-                // Ok(llm::InferenceFeedback::Halt)
-            }
-            llm::InferenceResponse::InferredToken(t) => {
-                print!("{t}");
-                std::io::stdout().flush().unwrap();
+) -> Result<llm::InferenceStats, AppError> {
+    tracing::debug!(
+        "Predict {prompt:#?} for conversation {conversation_id} with template {}",
+        template.name
+    );
+    tracing::debug!("Sampling parameters {sampling:#?}");
+    // Reset the cancellation flag of this window before starting
+    let stop_flag = state.stop_flag(win.label())?;
+    stop_flag.store(false, Ordering::SeqCst);
+    let app_handle = win.app_handle();
+    // The prediction takes a while, keep it away from the async runtime
+    let stats = tauri::async_runtime::spawn_blocking(move || {
+        let model = win.state::<Model>();
+        model.chat(
+            Some(conversation_id.as_str()),
+            &template,
+            &history,
+            &prompt,
+            &sampling,
+            &stop_flag,
+            |t| {
                 let _ = win
-                    .emit("predict_event", t.as_str())
+                    .emit("predict_event", t)
                     .map_err(|err| err.to_string());
-                Ok(llm::InferenceFeedback::Continue)
-                // Ok(llm::InferenceFeedback::Halt)
-            }
-            llm::InferenceResponse::EotToken => Ok(llm::InferenceFeedback::Halt),
-            _ => Ok(llm::InferenceFeedback::Continue),
-        },
-    );
+                llm::InferenceFeedback::Continue
+            },
+        )
+    })
+    .await
+    .map_err(AppError::internal)??;
+    record_inference(&app_handle, &stats).await;
+    Ok(stats)
+}
 
-    match res {
-        Ok(result) => {
-            tracing::debug!("\n{result}");
-            Ok(result)
-        }
-        Err(err) => {
-            tracing::error!("\n{err}");
-            Err("Error".to_string())
-        }
+/// Tokens of the text with the vocabulary of the loaded model
+#[tauri::command]
+pub async fn tokenize(
+    text: String,
+    state: tauri::State<'_, Model>,
+) -> Result<Vec<llm::TokenId>, AppError> {
+    state.tokenize(&text)
+}
+
+/// Tokens the conversation would take in the context if the `prompt` was sent
+#[tauri::command]
+pub async fn count_tokens(
+    history: Vec<ChatTurn>,
+    prompt: String,
+    template: PromptTemplate,
+    sampling: SamplingParameters,
+    state: tauri::State<'_, Model>,
+) -> Result<TokenCount, AppError> {
+    state.count_tokens(&template, &history, &prompt, &sampling)
+}
+
+#[tauri::command]
+pub async fn stop_prediction<R: Runtime>(
+    win: Window<R>,
+    state: tauri::State<'_, Model>,
+) -> Result<String, AppError> {
+    tracing::info!("Stopping prediction for window {}", win.label());
+    state.stop_flag(win.label())?.store(true, Ordering::SeqCst);
+    Ok(String::from("Prediction stopped"))
+}
+
+#[tauri::command]
+pub async fn reset_session(
+    conversation_id: String,
+    state: tauri::State<'_, Model>,
+) -> Result<String, AppError> {
+    tracing::info!("Resetting the session of conversation {conversation_id}");
+    state.reset_session(&conversation_id)?;
+    Ok(String::from("Session reset"))
+}
+
+#[tauri::command]
+pub async fn drop_session(
+    conversation_id: String,
+    state: tauri::State<'_, Model>,
+) -> Result<String, AppError> {
+    tracing::info!("Dropping the session of conversation {conversation_id}");
+    match state.drop_session(&conversation_id)? {
+        true => Ok(String::from("Session dropped")),
+        false => Ok(String::from("No session to drop")),
     }
 }
 
+/// Run the benchmark suite against the loaded model and store its result, the progress is
+/// emitted as `benchmark_progress` events and `stop_prediction` stops it
 #[tauri::command]
-pub async fn load_dynamic_model(params: ModelParametersWrapper, state: tauri::State<'_, Model>) -> Result<String, String> {
-    tracing::debug!("Loading model");
-    let model_config_guard = state.model_config.lock().map_err(|err| err.to_string())?;
-    let model_config = match model_config_guard.as_ref() {
-        Some(model_config) => model_config,
-        None => return Err("Model config not found".to_string()),
+pub async fn run_benchmark<R: Runtime>(
+    win: Window<R>,
+    config: BenchmarkConfig,
+    state: tauri::State<'_, Model>,
+    database: tauri::State<'_, Database>,
+) -> Result<BenchmarkResult, AppError> {
+    tracing::info!("Running the benchmark {config:?}");
+    let stop_flag = state.stop_flag(win.label())?;
+    stop_flag.store(false, Ordering::SeqCst);
+    // The predictions take a while, keep them away from the async runtime
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let model = win.state::<Model>();
+        benchmark::run(&model, &config, now_millis(), &stop_flag, &mut |done, total| {
+            let _ = win
+                .emit("benchmark_progress", BenchmarkProgress { done, total })
+                .map_err(|err| err.to_string());
+        })
+    })
+    .await
+    .map_err(AppError::internal)??;
+    tracing::info!(
+        "Benchmark of {}: prompt eval {:.2} tokens/s, generation {:.2} tokens/s",
+        result.model_name,
+        result.prompt_eval.mean,
+        result.generation.mean
+    );
+    // Get the database
+    let db = database.db.lock().await;
+    // Check if it exists
+    let db = match db.as_ref() {
+        Some(db) => db,
+        None => return Err(AppError::DbNotConnected),
     };
-    let mut model_params = params.model_params;
-    model_params.rope_overrides = Some(llm::RoPEOverrides::default());
-
-    tracing::info!("Got model_config: {:#?}", state.model_config);
-    tracing::info!("Got model_params: {:#?}", model_params);
-
-    let model = llm::load_dynamic(
-        Some(model_config.model_architecture),
-        &model_config.model_path,
-        model_config.tokenizer_source.clone(),
-        model_params,
-        llm::load_progress_callback_stdout,
-    )
-    .map_err(|err| err.to_string())?;
-    *state.model.lock().map_err(|err| err.to_string())? = Some(model);
+    insert_benchmark(db, result).await
+}
 
+#[tauri::command]
+pub async fn load_dynamic_model<R: Runtime>(
+    win: Window<R>,
+    params: ModelParams,
+    state: tauri::State<'_, Model>,
+    database: tauri::State<'_, Database>,
+) -> Result<String, AppError> {
+    tracing::debug!("Loading model");
+    let (load_aborted, aborted) = state.begin_load()?;
+    let lora_adapters = params.model_params.lora_adapters.clone().unwrap_or_default();
+    let model_params: llm::ModelParameters = params.model_params.into();
+    let app_handle = win.app_handle();
+    // Reading the weights takes a while, keep it away from the async runtime
+    let loading = tauri::async_runtime::spawn_blocking(move || {
+        let model = win.state::<Model>();
+        model.load(model_params, &load_aborted, &mut |progress| {
+            let _ = win
+                .emit("model_load_progress", progress)
+                .map_err(|err| err.to_string());
+        })
+    });
+    let loaded = match future::select(loading, aborted).await {
+        Either::Left((loaded, _)) => loaded,
+        Either::Right((Ok(()), _)) => return Err(AppError::LoadAborted),
+        // The loading ended without being aborted, its result is on the way
+        Either::Right((Err(_), loading)) => loading.await,
+    };
+    loaded.map_err(AppError::internal)??;
+    if let (false, Some(name)) = (lora_adapters.is_empty(), state.model_config_name()) {
+        // Get the database
+        let db = database.db.lock().await;
+        // Check if it exists
+        if let Some(db) = db.as_ref() {
+            record_lora_adapters(db, &name, &lora_adapters).await?;
+            app_handle
+                .emit_all("db_sync_event", ())
+                .map_err(AppError::internal)?;
+        }
+    }
     Ok(format!("Model loaded"))
 }
 
 #[tauri::command]
-pub async fn unload_dynamic_model(state: tauri::State<'_, Model>) -> Result<String, String> {
+pub async fn abort_model_load(state: tauri::State<'_, Model>) -> Result<String, AppError> {
+    tracing::info!("Aborting the model loading");
+    match state.abort_load()? {
+        true => Ok(String::from("Model loading aborted")),
+        false => Ok(String::from("No model loading to abort")),
+    }
+}
+
+#[tauri::command]
+pub async fn unload_dynamic_model(state: tauri::State<'_, Model>) -> Result<String, AppError> {
     tracing::info!("Unloading model");
-    *state.model.lock().map_err(|err| err.to_string())? = None;
+    state.unload()?;
     Ok(String::from("Model unloaded"))
 }
 
+/// Read the header of a model file to fill the model config before adding it
+#[tauri::command]
+pub async fn inspect_model_file(path: PathBuf) -> Result<ModelMetadata, AppError> {
+    tracing::info!("Inspecting {}", path.display());
+    tauri::async_runtime::spawn_blocking(move || inspect::inspect(&path))
+        .await
+        .map_err(AppError::internal)?
+}
+
+/// Architectures of the llm build, the others are refused when the model is loaded
+#[tauri::command]
+pub async fn list_supported_architectures() -> Result<Vec<ModelArchitecture>, AppError> {
+    Ok(ModelArchitecture::supported())
+}
+
+/// Read a LoRA adapter to check it before adding it to the load parameters
+#[tauri::command]
+pub async fn inspect_lora_adapter(path: PathBuf) -> Result<LoraMetadata, AppError> {
+    tracing::info!("Inspecting the LoRA adapter {}", path.display());
+    tauri::async_runtime::spawn_blocking(move || inspect::inspect_lora(&path))
+        .await
+        .map_err(AppError::internal)?
+}
+
 #[tauri::command]
 pub async fn load_model_config(
     model_config: ModelConfig,
     state: tauri::State<'_, Model>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     tracing::info!("Loading config {model_config:#?}");
-    *state.model_config.lock().map_err(|err| err.to_string())? = Some(model_config);
+    state.set_model_config(model_config)?;
     Ok(format!("Model config loaded"))
 }
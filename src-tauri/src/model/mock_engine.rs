@@ -0,0 +1,300 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::error::AppError;
+
+use super::{
+    engine::{InferenceEngine, LoadProgress, PredictRequest},
+    ModelConfig,
+};
+
+/// Deterministic engine for tests and development without model weights,
+/// it answers "You said: " followed by the fed prompt, one word per token
+#[derive(Default)]
+pub struct MockEngine {
+    // Name of the loaded model config
+    loaded: Mutex<Option<String>>,
+    // Conversations whose session already holds the full template
+    primed: Mutex<HashSet<String>>,
+}
+
+impl InferenceEngine for MockEngine {
+    fn load(
+        &self,
+        model_config: &ModelConfig,
+        _model_params: llm::ModelParameters,
+        aborted: &AtomicBool,
+        progress: &mut dyn FnMut(LoadProgress),
+    ) -> Result<(), AppError> {
+        tracing::info!("Mock loading of {}", model_config.name);
+        progress(LoadProgress::HyperparametersLoaded);
+        progress(LoadProgress::TensorLoaded {
+            current_tensor: 1,
+            tensor_count: 1,
+        });
+        if aborted.load(Ordering::SeqCst) {
+            return Err(AppError::LoadAborted);
+        }
+        progress(LoadProgress::Loaded {
+            bytes_read: 0,
+            tensor_count: 1,
+        });
+        self.primed.lock()?.clear();
+        *self.loaded.lock()? = Some(model_config.name.clone());
+        Ok(())
+    }
+
+    fn unload(&self) -> Result<(), AppError> {
+        self.primed.lock()?.clear();
+        *self.loaded.lock()? = None;
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.loaded
+            .lock()
+            .map(|loaded| loaded.is_some())
+            .unwrap_or_default()
+    }
+
+    fn threads(&self) -> usize {
+        1
+    }
+
+    fn predict(
+        &self,
+        request: PredictRequest<'_>,
+        on_token: &mut dyn FnMut(&str) -> llm::InferenceFeedback,
+    ) -> Result<llm::InferenceStats, AppError> {
+        if !self.is_loaded() {
+            return Err(AppError::ModelNotLoaded);
+        }
+        let mut primed = self.primed.lock()?;
+        let prompt = match (request.conversation_id, request.turn) {
+            (Some(conversation_id), Some(turn)) if primed.contains(conversation_id) => turn,
+            _ => request.message,
+        };
+        if let Some(conversation_id) = request.conversation_id {
+            primed.insert(conversation_id.to_owned());
+        }
+
+        let answer = format!("You said: {}", prompt.trim());
+        let maximum_token_count = request.sampling.maximum_token_count.unwrap_or(usize::MAX);
+        let mut predict_tokens = 0;
+        for (index, word) in answer.split_whitespace().enumerate() {
+            if predict_tokens >= maximum_token_count || request.stop_flag.load(Ordering::SeqCst) {
+                break;
+            }
+            let token = if index == 0 { word.to_owned() } else { format!(" {word}") };
+            predict_tokens += 1;
+            if let llm::InferenceFeedback::Halt = on_token(&token) {
+                break;
+            }
+        }
+        Ok(llm::InferenceStats {
+            prompt_tokens: self.tokenize(prompt)?.len(),
+            predict_tokens,
+            ..Default::default()
+        })
+    }
+
+    /// One token per word
+    fn tokenize(&self, text: &str) -> Result<Vec<llm::TokenId>, AppError> {
+        Ok((0..text.split_whitespace().count())
+            .map(|token_id| token_id as llm::TokenId)
+            .collect())
+    }
+
+    fn reset_session(&self, conversation_id: &str) -> Result<(), AppError> {
+        if !self.is_loaded() {
+            return Err(AppError::ModelNotLoaded);
+        }
+        self.primed.lock()?.remove(conversation_id);
+        Ok(())
+    }
+
+    fn drop_session(&self, conversation_id: &str) -> Result<bool, AppError> {
+        Ok(self
+            .primed
+            .lock()?
+            .remove(conversation_id))
+    }
+}
+
+/// A model loaded with the mock engine, for the tests of the modules using the `Model` state
+#[cfg(test)]
+pub(crate) fn loaded_model(name: &str) -> super::Model {
+    let model = super::Model::new(Box::<MockEngine>::default());
+    model
+        .set_model_config(ModelConfig {
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    model
+        .load(llm::ModelParameters::default(), &AtomicBool::new(false), &mut |_| ())
+        .unwrap();
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{
+            conversation::Entity,
+            prompt_template::{presets, PromptTemplate},
+        },
+        model::{template::ChatTurn, Model, ModelParameters, SamplingParameters},
+    };
+
+    fn predict(model: &Model, conversation_id: Option<&str>, sampling: &SamplingParameters) -> Result<String, AppError> {
+        let mut answer = String::new();
+        model.predict(
+            conversation_id,
+            "Hello world",
+            Some("Next turn"),
+            sampling,
+            &AtomicBool::new(false),
+            |t| {
+                answer.push_str(t);
+                llm::InferenceFeedback::Continue
+            },
+        )?;
+        Ok(answer)
+    }
+
+    #[test]
+    fn predict_needs_a_loaded_model() {
+        let model = Model::new(Box::<MockEngine>::default());
+        assert!(predict(&model, None, &SamplingParameters::default()).is_err());
+    }
+
+    #[test]
+    fn predict_streams_the_answer() {
+        let model = loaded_model("mock");
+        let answer = predict(&model, None, &SamplingParameters::default()).unwrap();
+        assert_eq!(answer, "You said: Hello world");
+    }
+
+    #[test]
+    fn predict_stops_at_the_maximum_token_count() {
+        let model = loaded_model("mock");
+        let sampling = SamplingParameters {
+            maximum_token_count: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(predict(&model, None, &sampling).unwrap(), "You said:");
+    }
+
+    #[test]
+    fn inference_records_need_a_loaded_model() {
+        let stats = llm::InferenceStats::default();
+        let model = Model::new(Box::<MockEngine>::default());
+        assert_eq!(model.inference_record(&stats, 0).unwrap(), None);
+        let model = loaded_model("mock");
+        let record = model.inference_record(&stats, 42).unwrap().unwrap();
+        assert_eq!((record.threads, record.timestamp), (1, 42));
+        assert_eq!(record.load_params, ModelParameters::default());
+        model.unload().unwrap();
+        assert_eq!(model.inference_record(&stats, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn predict_halts_on_the_stop_flag() {
+        let model = loaded_model("mock");
+        let stats = model
+            .predict(
+                None,
+                "Hello world",
+                None,
+                &SamplingParameters::default(),
+                &AtomicBool::new(true),
+                |_| llm::InferenceFeedback::Continue,
+            )
+            .unwrap();
+        assert_eq!(stats.predict_tokens, 0);
+    }
+
+    #[test]
+    fn predict_stops_before_the_stop_strings() {
+        let model = loaded_model("mock");
+        let sampling = SamplingParameters {
+            stop: vec!["llo wor".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(predict(&model, None, &sampling).unwrap(), "You said: He");
+    }
+
+    #[test]
+    fn count_tokens_of_the_rendered_conversation() {
+        let model = loaded_model("mock");
+        let template = PromptTemplate {
+            user: "{{PROMPT}} ".to_string(),
+            assistant: "{{PROMPT}} ".to_string(),
+            ..presets().pop().unwrap()
+        };
+        let history = [ChatTurn::new(Entity::User, "Hi"), ChatTurn::new(Entity::Bot, "Hello there")];
+        let sampling = SamplingParameters {
+            maximum_token_count: Some(2046),
+            ..Default::default()
+        };
+        let count = model.count_tokens(&template, &history, "Bye", &sampling).unwrap();
+        assert_eq!(count.used, 4);
+        assert_eq!(count.context_size, 2048);
+        assert!(count.overflows());
+        assert_eq!(count.available(), 2);
+    }
+
+    #[test]
+    fn kept_sessions_only_get_the_new_turn() {
+        let model = loaded_model("mock");
+        let sampling = SamplingParameters::default();
+        assert_eq!(predict(&model, Some("chat"), &sampling).unwrap(), "You said: Hello world");
+        assert_eq!(predict(&model, Some("chat"), &sampling).unwrap(), "You said: Next turn");
+        model.reset_session("chat").unwrap();
+        assert_eq!(predict(&model, Some("chat"), &sampling).unwrap(), "You said: Hello world");
+    }
+
+    #[test]
+    fn kept_sessions_are_not_fed_the_generated_stop_string_again() {
+        let model = loaded_model("mock");
+        // The answer is closed by " END", the stop string of the template
+        let template = PromptTemplate {
+            user: "{{PROMPT}}".to_string(),
+            assistant: "{{PROMPT}} END".to_string(),
+            stop: vec!["END".to_string()],
+            ..presets().pop().unwrap()
+        };
+        let chat = |history: &[ChatTurn], prompt: &str| {
+            let mut answer = String::new();
+            model
+                .chat(
+                    Some("chat"),
+                    &template,
+                    history,
+                    prompt,
+                    &SamplingParameters::default(),
+                    &AtomicBool::new(false),
+                    |t| {
+                        answer.push_str(t);
+                        llm::InferenceFeedback::Continue
+                    },
+                )
+                .unwrap();
+            answer
+        };
+        assert_eq!(chat(&[], "Say END"), "You said: Say ");
+        // The session already holds " END", the mock echoes that it was only fed the prompt
+        let history = [
+            ChatTurn::new(Entity::User, "Say END"),
+            ChatTurn::new(Entity::Bot, "You said: Say"),
+        ];
+        assert_eq!(chat(&history, "Bye"), "You said: Bye");
+    }
+}
@@ -1,86 +1,120 @@
 use std::{
-    path::PathBuf,
-    sync::{Arc, Mutex}
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
 };
-use serde::{Deserialize, Serialize};
 use tauri::{App, Manager};
+use tokio::sync::oneshot;
 
+pub mod benchmark;
+pub mod engine;
+pub mod inspect;
+pub mod llm_engine;
 pub mod logic;
-pub mod simulated;
-
-// For future llm commits
-#[derive(Deserialize, Debug, Default)]
-#[serde(remote="llm::ModelParameters")]
-pub struct ModelParameters {
-    pub prefer_mmap: bool,
-    pub context_size: usize,
-    pub lora_adapters: Option<Vec<PathBuf>>,
-    pub use_gpu: bool,
-    pub gpu_layers: Option<usize>,
-    #[serde(skip)]
-    pub rope_overrides: Option<llm::RoPEOverrides>
-}
+pub mod mock_engine;
+pub mod stop;
+pub mod template;
 
-#[derive(Deserialize)]
-pub struct ModelParametersWrapper{
-    #[serde(with = "ModelParameters")]
-    model_params: llm::ModelParameters
-}
+use crate::{db::prompt_template::PromptTemplate, error::AppError};
+use engine::{InferenceEngine, LoadProgress, PredictRequest};
+use stop::StopMatcher;
+use template::{render, render_truncated, render_turn, ChatTurn};
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-#[serde(remote="llm::ModelArchitecture")]
-pub enum ModelArchitecture {
-    Bloom,
-    Gpt2,
-    GptJ,
-    GptNeoX,
-    #[default]
-    Llama,
-    Mpt,
-    // Falcon
-}
+pub use personal_assistant_types::{
+    BenchmarkConfig, BenchmarkProgress, BenchmarkResult, BenchmarkSample, ExportFormat, InferenceRecord,
+    InferenceStats, ModelArchitecture, ModelConfig, ModelParameters, ModelParams, RoPEOverrides,
+    SamplingParameters, TokenCount, TokenizerSource, TopPTopK, DEFAULT_TRAINED_CONTEXT,
+};
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-#[serde(remote="llm::TokenizerSource")]
-pub enum TokenizerSource {
-    #[default]
-    Embedded,
-    HuggingFaceTokenizerFile(PathBuf),
-    HuggingFaceRemote(String),
-    HuggingFaceTokenizerString(String),
-}
+/// Tokens kept for the answer when the maximum token count is not set
+pub const ANSWER_RESERVE: usize = 256;
 
+/// Check that the tokenizer can be built before saving a model config. A remote tokenizer is
+/// only checked for its name, it is downloaded when the model is loaded.
+pub fn check_tokenizer_source(
+    tokenizer_source: &TokenizerSource,
+    model_path: &Path,
+) -> Result<(), AppError> {
+    match tokenizer_source {
+        TokenizerSource::Embedded => return Ok(()),
+        TokenizerSource::HuggingFaceRemote(identifier) => {
+            return match identifier.trim().is_empty() || identifier.contains(char::is_whitespace) {
+                true => Err(AppError::invalid_input(
+                    "tokenizer",
+                    format!("{identifier:?} is not a Hugging Face repository name"),
+                )),
+                false => Ok(()),
+            };
+        }
+        TokenizerSource::HuggingFaceTokenizerFile(path) if !path.is_file() => {
+            return Err(AppError::invalid_input(
+                "tokenizer",
+                format!("{} is not a file", path.display()),
+            ));
+        }
+        _ => (),
+    }
+    llm::TokenizerSource::from(tokenizer_source.clone())
+        .retrieve(model_path)
+        .map(|_| ())
+        .map_err(|err| AppError::invalid_input("tokenizer", err.to_string()))
+}
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ModelConfig {
-    pub name: String,
-    #[serde(with = "ModelArchitecture")]
-    pub model_architecture: llm::ModelArchitecture,
-    pub model_path: PathBuf,
-    #[serde(with = "TokenizerSource")]
-    pub tokenizer_source: llm::TokenizerSource,
+/// A model loading, a new one is refused until its blocking task returns
+struct Loading {
+    // Own flag of the loading, an aborted loading never installs its model
+    aborted: Arc<AtomicBool>,
+    abort: Option<oneshot::Sender<()>>,
 }
 
+/// Ends the in-flight loading when the blocking loading returns or panics
+struct EndLoading<'a>(&'a Mutex<Option<Loading>>);
 
-impl Default for ModelConfig {
-    fn default() -> Self {
-        Self {
-            name: String::new(),
-            model_architecture: llm::ModelArchitecture::Llama,
-            model_path: PathBuf::default(),
-            tokenizer_source: llm::TokenizerSource::Embedded,
-        }
+impl Drop for EndLoading<'_> {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = None;
     }
 }
 
-#[derive(Default)]
 pub struct Model {
-    model: Arc<Mutex<Option<Box<dyn llm::Model>>>>,
+    engine: Box<dyn InferenceEngine>,
     model_config: Arc<Mutex<Option<ModelConfig>>>,
+    // Cancellation flags of the running predictions, one per window label
+    stop_flags: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    // Generated text of the kept sessions whose prediction ended on a stop string, with the
+    // length of its end from the stop string on
+    stopped_sessions: Arc<Mutex<HashMap<String, (String, usize)>>>,
+    // The in-flight model loading
+    loading: Arc<Mutex<Option<Loading>>>,
+    // Context size of the loaded model
+    context_size: Arc<AtomicUsize>,
+    // Parameters the loaded model was loaded with
+    load_params: Arc<Mutex<Option<ModelParameters>>>,
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Self::new(engine::default_engine())
+    }
 }
 
 impl Model {
-    pub fn init(app: &App) -> Result<(), String>{
+    pub fn new(engine: Box<dyn InferenceEngine>) -> Self {
+        Self {
+            engine,
+            model_config: Default::default(),
+            stop_flags: Default::default(),
+            stopped_sessions: Default::default(),
+            loading: Default::default(),
+            context_size: Default::default(),
+            load_params: Default::default(),
+        }
+    }
+
+    pub fn init(app: &App) -> Result<(), AppError>{
         // app.manage(Model{
         //     model_config: Arc::new(Mutex::new(Some(ModelConfig::default()))),
         //     ..Default::default()
@@ -88,6 +122,314 @@ impl Model {
         app.manage(Model::default());
         Ok(())
     }
-    
+
+    pub fn set_model_config(&self, model_config: ModelConfig) -> Result<(), AppError> {
+        *self.model_config.lock()? = Some(model_config);
+        Ok(())
+    }
+
+    /// Load the model of the current model config, blocking until it is loaded. The model is not
+    /// installed once `aborted` is set, see `begin_load`.
+    pub fn load(
+        &self,
+        model_params: llm::ModelParameters,
+        aborted: &AtomicBool,
+        progress: &mut dyn FnMut(LoadProgress),
+    ) -> Result<(), AppError> {
+        let _end_loading = EndLoading(&self.loading);
+        let model_config = match self.model_config.lock()?.clone() {
+            Some(model_config) => model_config,
+            None => return Err(AppError::NoModelConfig),
+        };
+        let load_params = ModelParameters::from(&model_params);
+        self.engine
+            .load(&model_config, model_params, aborted, progress)?;
+        self.context_size.store(load_params.context_size, Ordering::SeqCst);
+        *self.load_params.lock()? = Some(load_params);
+        Ok(())
+    }
+
+    /// Context size of the loaded model
+    pub fn context_size(&self) -> usize {
+        self.context_size.load(Ordering::SeqCst)
+    }
+
+    /// Prepare a new loading, refused until the blocking task of the previous one returned.
+    /// Returns the abort flag to hand to `load` and a receiver resolving when it is aborted.
+    pub fn begin_load(&self) -> Result<(Arc<AtomicBool>, oneshot::Receiver<()>), AppError> {
+        let mut loading = self.loading.lock()?;
+        if loading.is_some() {
+            return Err(AppError::LoadInProgress);
+        }
+        let aborted = Arc::new(AtomicBool::new(false));
+        let (abort, on_abort) = oneshot::channel();
+        *loading = Some(Loading {
+            aborted: aborted.clone(),
+            abort: Some(abort),
+        });
+        Ok((aborted, on_abort))
+    }
+
+    /// Abort the in-flight loading, returns whether there was one to abort
+    pub fn abort_load(&self) -> Result<bool, AppError> {
+        match self.loading.lock()?.as_mut() {
+            Some(loading) => {
+                loading.aborted.store(true, Ordering::SeqCst);
+                Ok(loading.abort.take().is_some_and(|abort| abort.send(()).is_ok()))
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn unload(&self) -> Result<(), AppError> {
+        self.engine.unload()
+    }
+
+    /// Get the cancellation flag of the given window, creating it if needed
+    pub fn stop_flag(&self, label: &str) -> Result<Arc<AtomicBool>, AppError> {
+        let mut stop_flags = self.stop_flags.lock()?;
+        Ok(stop_flags
+            .entry(label.to_owned())
+            .or_insert_with(|| Arc::new(AtomicBool::new(false)))
+            .clone())
+    }
+
+    /// Run a prediction with the loaded model, the inferred tokens are streamed to `on_token` and
+    /// the stop strings of the sampling parameters are never streamed.
+    /// With a `conversation_id` the session is kept for the next call, the full `message` is fed
+    /// to a new session and only the `turn` is fed to a kept one.
+    pub fn predict(
+        &self,
+        conversation_id: Option<&str>,
+        message: &str,
+        turn: Option<&str>,
+        sampling: &SamplingParameters,
+        stop_flag: &AtomicBool,
+        mut on_token: impl FnMut(&str) -> llm::InferenceFeedback,
+    ) -> Result<llm::InferenceStats, AppError> {
+        let turn = match (conversation_id, turn) {
+            (Some(conversation_id), Some(turn)) => Some(self.unfed_turn(conversation_id, turn)?),
+            _ => turn,
+        };
+        let mut stop_matcher = StopMatcher::new(sampling.stop.iter().cloned());
+        let mut generated = String::new();
+        let mut emitted_len = 0;
+        let mut stop_len = None;
+        let stats = self.engine.predict(
+            PredictRequest {
+                conversation_id,
+                message,
+                turn,
+                sampling,
+                stop_flag,
+            },
+            &mut |t| {
+                generated.push_str(t);
+                let (text, stopped) = stop_matcher.feed(t);
+                emitted_len += text.len();
+                if stopped {
+                    stop_len = Some(generated.len() - emitted_len);
+                }
+                let feedback = match text.is_empty() {
+                    true => llm::InferenceFeedback::Continue,
+                    false => on_token(&text),
+                };
+                match stopped {
+                    true => llm::InferenceFeedback::Halt,
+                    false => feedback,
+                }
+            },
+        )?;
+        // The end of the answer looked like the start of a stop string
+        let held_back = stop_matcher.flush();
+        if !held_back.is_empty() {
+            on_token(&held_back);
+        }
+        if let (Some(conversation_id), Some(stop_len)) = (conversation_id, stop_len) {
+            self.stopped_sessions
+                .lock()?
+                .insert(conversation_id.to_owned(), (generated, stop_len));
+        }
+        Ok(stats)
+    }
+
+    /// The part of the `turn` a kept session was not fed yet. A prediction that ended on a stop
+    /// string already fed it and what followed in its token, when that is not the start of the
+    /// turn the session starts over from the full message.
+    fn unfed_turn<'a>(&self, conversation_id: &str, turn: &'a str) -> Result<&'a str, AppError> {
+        let Some((generated, stop_len)) = self.stopped_sessions.lock()?.remove(conversation_id)
+        else {
+            return Ok(turn);
+        };
+        match stop::generated_turn_len(&generated, stop_len, turn) {
+            Some(len) => Ok(&turn[len..]),
+            None => {
+                self.engine.drop_session(conversation_id)?;
+                Ok(turn)
+            }
+        }
+    }
+
+    /// Answer the `prompt` of a chat. A new session is fed the whole conversation rendered with
+    /// the template, without the oldest turns that do not fit in the context, a kept session is
+    /// only fed the new turn.
+    #[allow(clippy::too_many_arguments)]
+    pub fn chat(
+        &self,
+        conversation_id: Option<&str>,
+        template: &PromptTemplate,
+        history: &[ChatTurn],
+        prompt: &str,
+        sampling: &SamplingParameters,
+        stop_flag: &AtomicBool,
+        on_token: impl FnMut(&str) -> llm::InferenceFeedback,
+    ) -> Result<llm::InferenceStats, AppError> {
+        let count = self.count_tokens(template, history, prompt, sampling)?;
+        let message = match count.overflows() {
+            false => render(template, history, prompt),
+            true => {
+                // The kept session would overflow as well, start over with the recent turns
+                if let Some(conversation_id) = conversation_id {
+                    self.drop_session(conversation_id)?;
+                }
+                render_truncated(template, history, prompt, count.available(), &mut |text| {
+                    Ok(self.tokenize(text)?.len())
+                })?
+            }
+        };
+        let turn = render_turn(template, prompt);
+        // The template ends a turn with its own stop strings
+        let sampling = SamplingParameters {
+            stop: template.stop.iter().chain(&sampling.stop).cloned().collect(),
+            ..sampling.clone()
+        };
+        self.predict(
+            conversation_id,
+            &message,
+            Some(&turn),
+            &sampling,
+            stop_flag,
+            on_token,
+        )
+    }
+
+    pub fn tokenize(&self, text: &str) -> Result<Vec<llm::TokenId>, AppError> {
+        self.engine.tokenize(text)
+    }
+
+    /// Count the tokens of the whole conversation as a new session would be fed
+    pub fn count_tokens(
+        &self,
+        template: &PromptTemplate,
+        history: &[ChatTurn],
+        prompt: &str,
+        sampling: &SamplingParameters,
+    ) -> Result<TokenCount, AppError> {
+        Ok(TokenCount {
+            used: self.tokenize(&render(template, history, prompt))?.len(),
+            reserved: sampling.maximum_token_count.unwrap_or(ANSWER_RESERVE),
+            context_size: self.context_size(),
+        })
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.engine.is_loaded()
+    }
+
+    /// Name of the loaded model config
+    pub fn model_config_name(&self) -> Option<String> {
+        self.model_config
+            .lock()
+            .ok()?
+            .as_ref()
+            .map(|model_config| model_config.name.clone())
+    }
+
+    /// Prompt template picked for the loaded model config
+    pub fn template_name(&self) -> Option<String> {
+        self.model_config
+            .lock()
+            .ok()?
+            .as_ref()?
+            .template_name
+            .clone()
+    }
+
+    /// Stats of a prediction of the loaded model with how it was loaded, `None` without a
+    /// loaded model
+    pub fn inference_record(
+        &self,
+        stats: &llm::InferenceStats,
+        timestamp: i64,
+    ) -> Result<Option<InferenceRecord>, AppError> {
+        let model_config = self.model_config.lock()?.clone();
+        let load_params = self.load_params.lock()?.clone();
+        let (Some(model_config), Some(load_params), true) =
+            (model_config, load_params, self.is_loaded())
+        else {
+            return Ok(None);
+        };
+        Ok(Some(InferenceRecord {
+            model_name: model_config.name,
+            model_architecture: model_config.model_architecture,
+            load_params,
+            threads: self.engine.threads(),
+            timestamp,
+            stats: stats.into(),
+        }))
+    }
+
+    /// Start a new session for the conversation, the next prediction feeds the full template
+    pub fn reset_session(&self, conversation_id: &str) -> Result<(), AppError> {
+        self.stopped_sessions.lock()?.remove(conversation_id);
+        self.engine.reset_session(conversation_id)
+    }
+
+    /// Free the session of the conversation, returns whether there was one
+    pub fn drop_session(&self, conversation_id: &str) -> Result<bool, AppError> {
+        self.stopped_sessions.lock()?.remove(conversation_id);
+        self.engine.drop_session(conversation_id)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn invalid_tokenizers_are_rejected() {
+        let model_path = Path::new("/models/llama.bin");
+        assert!(check_tokenizer_source(&TokenizerSource::Embedded, model_path).is_ok());
+        for tokenizer_source in [
+            TokenizerSource::HuggingFaceTokenizerFile(PathBuf::from("/missing/tokenizer.json")),
+            TokenizerSource::HuggingFaceTokenizerString("not json".to_string()),
+            TokenizerSource::HuggingFaceRemote(" ".to_string()),
+        ] {
+            let err = check_tokenizer_source(&tokenizer_source, model_path).unwrap_err();
+            assert!(matches!(err, AppError::InvalidInput { .. }), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn an_aborted_loading_never_installs_its_model() {
+        let model = Model::new(Box::<mock_engine::MockEngine>::default());
+        model.set_model_config(ModelConfig::default()).unwrap();
+        let (aborted, _on_abort) = model.begin_load().unwrap();
+        assert!(matches!(model.begin_load(), Err(AppError::LoadInProgress)));
+        let err = model
+            .load(Default::default(), &aborted, &mut |_| {
+                model.abort_load().unwrap();
+            })
+            .unwrap_err();
+        assert!(matches!(err, AppError::LoadAborted));
+        assert!(!model.is_loaded());
+        assert_eq!(model.context_size(), 0);
+
+        // The next loading has its own flag once the aborted one returned
+        let (aborted, _on_abort) = model.begin_load().unwrap();
+        model.load(Default::default(), &aborted, &mut |_| ()).unwrap();
+        assert!(model.is_loaded());
+        assert!(!model.abort_load().unwrap());
+    }
+}
@@ -0,0 +1,140 @@
+/// Watch the inferred text for stop strings. The text that could be the start of a stop string is
+/// held back until the next tokens tell whether it is one, so a stop string is never emitted.
+#[derive(Debug, Default)]
+pub struct StopMatcher {
+    stops: Vec<String>,
+    pending: String,
+}
+
+impl StopMatcher {
+    pub fn new(stops: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            stops: stops.into_iter().filter(|stop| !stop.is_empty()).collect(),
+            pending: String::new(),
+        }
+    }
+
+    /// Feed an inferred token, returns the text safe to emit and whether a stop string was found,
+    /// in which case the text after it is dropped
+    pub fn feed(&mut self, token: &str) -> (String, bool) {
+        self.pending.push_str(token);
+        let found = self
+            .stops
+            .iter()
+            .filter_map(|stop| self.pending.find(stop.as_str()))
+            .min();
+        if let Some(position) = found {
+            let text = self.pending[..position].to_owned();
+            self.pending.clear();
+            return (text, true);
+        }
+        let held = self.partial_match_len();
+        let text = self.pending[..self.pending.len() - held].to_owned();
+        self.pending.drain(..self.pending.len() - held);
+        (text, false)
+    }
+
+    /// The text held back when the prediction ended without a stop string
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Length of the longest end of the pending text that starts a stop string
+    fn partial_match_len(&self) -> usize {
+        self.pending
+            .char_indices()
+            .map(|(position, _)| &self.pending[position..])
+            .find(|end| self.stops.iter().any(|stop| stop.starts_with(end)))
+            .map(str::len)
+            .unwrap_or_default()
+    }
+}
+
+/// Length of the start of `turn` a session already holds when its prediction ended on a stop
+/// string. The session was fed the `generated` text, whose last `stop_len` bytes start with the
+/// stop string, so the turn has to start with at least these bytes. `None` when it does not.
+pub fn generated_turn_len(generated: &str, stop_len: usize, turn: &str) -> Option<usize> {
+    (stop_len..=generated.len().min(turn.len()))
+        .rev()
+        .find(|&len| turn.is_char_boundary(len) && generated.ends_with(&turn[..len]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed all the tokens, returns the emitted text and whether it stopped
+    fn run(stops: &[&str], tokens: &[&str]) -> (String, bool) {
+        let mut matcher = StopMatcher::new(stops.iter().map(|stop| stop.to_string()));
+        let mut emitted = String::new();
+        for token in tokens {
+            let (text, stopped) = matcher.feed(token);
+            emitted.push_str(&text);
+            if stopped {
+                return (emitted, true);
+            }
+        }
+        emitted.push_str(&matcher.flush());
+        (emitted, false)
+    }
+
+    #[test]
+    fn without_stop_strings_everything_is_emitted() {
+        assert_eq!(run(&[], &["Hello", " world"]), ("Hello world".to_string(), false));
+    }
+
+    #[test]
+    fn stop_string_in_a_single_token() {
+        assert_eq!(
+            run(&["User:"], &["Fine", "\nUser:", " and you"]),
+            ("Fine\n".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn stop_string_split_across_tokens() {
+        assert_eq!(
+            run(&["<|im_end|>"], &["Fine", "<|", "im", "_end", "|>", "\n"]),
+            ("Fine".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn partial_match_is_held_back() {
+        let mut matcher = StopMatcher::new(["User:".to_string()]);
+        assert_eq!(matcher.feed("Fine\nUs"), ("Fine\n".to_string(), false));
+        assert_eq!(matcher.feed("e"), (String::new(), false));
+        assert_eq!(matcher.feed("ful"), ("Useful".to_string(), false));
+    }
+
+    #[test]
+    fn held_back_text_is_flushed_at_the_end() {
+        assert_eq!(run(&["User:"], &["Fine", "\nUs"]), ("Fine\nUs".to_string(), false));
+    }
+
+    #[test]
+    fn earliest_stop_string_wins() {
+        assert_eq!(
+            run(&["###", "\n\n"], &["Done", "\n", "\n###"]),
+            ("Done".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn generated_start_of_the_turn() {
+        // The answer ended on "<|im_end|>", the turn starts by closing the answer
+        let turn = "<|im_end|>\n<|im_start|>user\nBye<|im_end|>\n";
+        assert_eq!(generated_turn_len("Fine<|im_end|>", 10, turn), Some(10));
+        assert_eq!(generated_turn_len("Fine<|im_end|>\n", 11, turn), Some(11));
+        // "User:" is preceded by the end of the answer, the turn starts with it as well
+        assert_eq!(generated_turn_len("Fine\nUser:", 5, "\nUser: Bye\n"), Some(6));
+        // The token of the stop string went on with text the turn does not start with
+        assert_eq!(generated_turn_len("Fine\nUser: Hi", 8, "\nUser: Bye\n"), None);
+    }
+
+    #[test]
+    fn multi_byte_characters_are_not_split() {
+        assert_eq!(run(&["éé"], &["café", "é"]), ("caf".to_string(), true));
+        assert_eq!(run(&["éx"], &["café", "!"]), ("café!".to_string(), false));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    db::{
+        conversation::Entity,
+        prompt_template::{
+            PromptTemplate, HISTORY_PLACEHOLDER, PROMPT_PLACEHOLDER, SYSTEM_PLACEHOLDER,
+        },
+    },
+    error::AppError,
+};
+
+pub use personal_assistant_types::ChatTurn;
+
+/// Replace the placeholders in a single pass, so a message containing a placeholder is kept as is
+fn fill(text: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let next = values
+            .iter()
+            .filter_map(|(placeholder, value)| {
+                rest.find(placeholder)
+                    .map(|position| (position, *placeholder, *value))
+            })
+            .min_by_key(|(position, _, _)| *position);
+        match next {
+            Some((position, placeholder, value)) => {
+                filled.push_str(&rest[..position]);
+                filled.push_str(value);
+                rest = &rest[position + placeholder.len()..];
+            }
+            None => {
+                filled.push_str(rest);
+                return filled;
+            }
+        }
+    }
+}
+
+/// Text of the assistant part before and after `{{PROMPT}}`
+fn assistant_parts(template: &PromptTemplate) -> (&str, &str) {
+    template
+        .assistant
+        .split_once(PROMPT_PLACEHOLDER)
+        .unwrap_or((template.assistant.as_str(), ""))
+}
+
+fn render_turn_part(part: &str, content: &str) -> String {
+    fill(part, &[(PROMPT_PLACEHOLDER, content)])
+}
+
+/// Previous turns, each one with the part of its role
+pub fn render_history(template: &PromptTemplate, history: &[ChatTurn]) -> String {
+    history
+        .iter()
+        .map(|turn| match turn.entity {
+            Entity::User => render_turn_part(&template.user, &turn.content),
+            Entity::Bot => render_turn_part(&template.assistant, &turn.content),
+        })
+        .collect()
+}
+
+/// The new user turn followed by the start of the answer
+fn render_prompt(template: &PromptTemplate, prompt: &str) -> String {
+    let (assistant_prefix, _) = assistant_parts(template);
+    format!("{}{assistant_prefix}", render_turn_part(&template.user, prompt))
+}
+
+/// Full prompt of a new session
+pub fn render(template: &PromptTemplate, history: &[ChatTurn], prompt: &str) -> String {
+    fill(
+        &template.layout,
+        &[
+            (SYSTEM_PLACEHOLDER, &template.system),
+            (HISTORY_PLACEHOLDER, &render_history(template, history)),
+            (PROMPT_PLACEHOLDER, &render_prompt(template, prompt)),
+        ],
+    )
+}
+
+/// Prompt fed to a kept session, it closes the previous answer first
+pub fn render_turn(template: &PromptTemplate, prompt: &str) -> String {
+    let (_, assistant_suffix) = assistant_parts(template);
+    format!("{assistant_suffix}{}", render_prompt(template, prompt))
+}
+
+/// Render the conversation in at most `max_tokens`, dropping the oldest turns first. The system
+/// part and the new prompt are always kept and the history always starts with a user turn.
+pub fn render_truncated(
+    template: &PromptTemplate,
+    history: &[ChatTurn],
+    prompt: &str,
+    max_tokens: usize,
+    count_tokens: &mut dyn FnMut(&str) -> Result<usize, AppError>,
+) -> Result<String, AppError> {
+    let mut start = 0;
+    loop {
+        let rendered = render(template, &history[start..], prompt);
+        if count_tokens(&rendered)? <= max_tokens {
+            if start > 0 {
+                tracing::info!("Dropped the {start} oldest turns to fit {max_tokens} tokens");
+            }
+            return Ok(rendered);
+        }
+        if start == history.len() {
+            return Err(AppError::invalid_input(
+                "prompt",
+                format!("it does not fit in the {max_tokens} tokens left in the context"),
+            ));
+        }
+        start += 1;
+        while start < history.len() && history[start].entity != Entity::User {
+            start += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::prompt_template::presets;
+
+    fn preset(name: &str) -> PromptTemplate {
+        presets()
+            .into_iter()
+            .find(|preset| preset.name == name)
+            .unwrap()
+    }
+
+    fn words(text: &str) -> Result<usize, AppError> {
+        Ok(text.split_whitespace().count())
+    }
+
+    fn history() -> Vec<ChatTurn> {
+        vec![
+            ChatTurn::new(Entity::User, "Hi"),
+            ChatTurn::new(Entity::Bot, "Hello"),
+            ChatTurn::new(Entity::User, "How are you"),
+            ChatTurn::new(Entity::Bot, "Fine"),
+        ]
+    }
+
+    #[test]
+    fn render_without_history() {
+        assert_eq!(
+            render(&preset("ChatML"), &[], "Hi"),
+            "<|im_start|>system\nYou are a helpful assistant.<|im_end|>\n<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn render_with_history() {
+        assert_eq!(
+            render(&preset("Alpaca"), &history()[..2], "Bye"),
+            "Below is an instruction that describes a task. Write a response that appropriately completes the request.\n\n### Instruction:\nHi\n\n### Response:\nHello\n\n### Instruction:\nBye\n\n### Response:\n"
+        );
+    }
+
+    #[test]
+    fn render_follows_the_layout() {
+        let template = PromptTemplate {
+            layout: "{{HISTORY}}{{SYSTEM}}{{PROMPT}}".to_string(),
+            system: "[system]".to_string(),
+            ..preset("Plain")
+        };
+        assert_eq!(render(&template, &history()[..2], "Bye"), "HiHello[system]Bye");
+    }
+
+    #[test]
+    fn render_keeps_placeholders_of_the_messages() {
+        let history = vec![ChatTurn::new(Entity::User, "{{SYSTEM}}")];
+        assert_eq!(
+            render(&preset("Plain"), &history, "{{HISTORY}}"),
+            "{{SYSTEM}}{{HISTORY}}"
+        );
+    }
+
+    #[test]
+    fn render_turn_closes_the_previous_answer() {
+        assert_eq!(
+            render_turn(&preset("ChatML"), "Bye"),
+            "<|im_end|>\n<|im_start|>user\nBye<|im_end|>\n<|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn truncation_keeps_everything_that_fits() {
+        let template = preset("Plain");
+        let rendered = render_truncated(&template, &history(), "Bye", 100, &mut words).unwrap();
+        assert_eq!(rendered, render(&template, &history(), "Bye"));
+    }
+
+    #[test]
+    fn truncation_drops_the_oldest_turns() {
+        let template = PromptTemplate {
+            user: "{{PROMPT}} ".to_string(),
+            assistant: "{{PROMPT}} ".to_string(),
+            ..preset("Plain")
+        };
+        // "How are you Fine Bye" is 5 words
+        let rendered = render_truncated(&template, &history(), "Bye", 5, &mut words).unwrap();
+        assert_eq!(rendered, "How are you Fine Bye ");
+    }
+
+    #[test]
+    fn truncation_starts_the_history_with_a_user_turn() {
+        let template = PromptTemplate {
+            user: "{{PROMPT}} ".to_string(),
+            assistant: "{{PROMPT}} ".to_string(),
+            ..preset("Plain")
+        };
+        // Dropping "Hi" alone would fit but leave "Hello" without its question
+        let rendered = render_truncated(&template, &history(), "Bye", 6, &mut words).unwrap();
+        assert_eq!(rendered, "How are you Fine Bye ");
+    }
+
+    #[test]
+    fn truncation_fails_when_the_prompt_does_not_fit() {
+        let result = render_truncated(&preset("Plain"), &history(), "One two three", 2, &mut words);
+        assert!(matches!(result, Err(AppError::InvalidInput { .. })));
+    }
+}
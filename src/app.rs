@@ -1,7 +1,7 @@
 use leptos::*;
 use leptos_router::*;
 use leptos_meta::*;
-use personal_assistant_ui::{pages::{Conversation, Setting}, components::NavBar, setup};
+use personal_assistant_ui::{pages::{Conversation, Setting, Stats}, components::NavBar, setup};
 
 #[component]
 pub fn App(cx: Scope) -> impl IntoView {
@@ -9,6 +9,7 @@ pub fn App(cx: Scope) -> impl IntoView {
     let routes = vec![
         ("/".to_owned(), "Setting".to_owned()),
         ("/conversation".to_owned(), "Conversation".to_owned()),
+        ("/stats".to_owned(), "Stats".to_owned()),
     ];
     let (is_model_connected, _) = use_context::<(ReadSignal<bool>, WriteSignal<bool>)>(cx)
             .expect("to have found the getter provided for model status");
@@ -22,6 +23,7 @@ pub fn App(cx: Scope) -> impl IntoView {
                 <Routes>
                     <Route path="/" view=|cx| view! { cx, <Setting/> }/>
                     <Route path="/conversation" view=|cx| view! { cx, <Conversation/> }/>
+                    <Route path="/stats" view=|cx| view! { cx, <Stats/> }/>
                 </Routes>
             </main>
             <footer class="flex-0 flex flex-col">
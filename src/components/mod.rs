@@ -1,5 +1,7 @@
 mod chat;
 mod nav_bar;
+mod side_bar;
 
 pub use chat::Chat;
 pub use nav_bar::NavBar;
+pub use side_bar::SideBar;
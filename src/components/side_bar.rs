@@ -0,0 +1,143 @@
+use leptos::*;
+use leptos_icons::*;
+
+use crate::{
+    invoke, Conversation, ConversationId, Message, PayloadConversationId, PayloadRenameConversation,
+    StoredMessage,
+};
+
+#[component]
+pub fn SideBar(cx: Scope, disabled: ReadSignal<bool>) -> impl IntoView {
+    let (conversations, _) =
+        use_context::<(ReadSignal<Vec<Conversation>>, WriteSignal<Vec<Conversation>>)>(cx)
+            .expect("to have found the setter and getter provided for conversations");
+    let (conversation_id, set_conversation_id) =
+        use_context::<(ReadSignal<ConversationId>, WriteSignal<ConversationId>)>(cx)
+            .expect("to have found the setter and getter provided for the conversation id");
+    let (_, set_messages) =
+        use_context::<(ReadSignal<Vec<Message>>, WriteSignal<Vec<Message>>)>(cx)
+            .expect("to have found the setter and getter provided for messages");
+    // Conversation being renamed
+    let (editing, set_editing) = create_signal(cx, None::<String>);
+
+    let on_click_select = move |selected_conversation_id: String| {
+        log!("on_click_select: {selected_conversation_id}");
+        spawn_local(async move {
+            let payload = PayloadConversationId {
+                conversation_id: selected_conversation_id.clone(),
+            };
+            match invoke::<_, Vec<StoredMessage>>("load_conversation", &payload).await {
+                Ok(stored_messages) => {
+                    set_messages(stored_messages.into_iter().map(Message::from).collect());
+                    set_conversation_id(ConversationId(Some(selected_conversation_id)));
+                }
+                Err(err) => error!("Got an error while invoking load_conversation: {err}"),
+            };
+        });
+    };
+
+    let on_change_title = move |selected_conversation_id: String, title: String| {
+        set_editing(None);
+        spawn_local(async move {
+            let payload = PayloadRenameConversation {
+                conversation_id: selected_conversation_id,
+                title,
+            };
+            match invoke::<_, Conversation>("rename_conversation", &payload).await {
+                Ok(conversation) => log!("Conversation renamed: {conversation:#?}"),
+                Err(err) => error!("Got an error while invoking rename_conversation: {err}"),
+            };
+        });
+    };
+
+    let on_click_delete = move |selected_conversation_id: String| {
+        log!("on_click_delete: {selected_conversation_id}");
+        if conversation_id().0.as_ref() == Some(&selected_conversation_id) {
+            set_conversation_id(ConversationId::default());
+            set_messages.update(|messages| messages.clear());
+        }
+        spawn_local(async move {
+            let payload = PayloadConversationId {
+                conversation_id: selected_conversation_id,
+            };
+            match invoke::<_, String>("delete_conversation", &payload).await {
+                Ok(msg) => log!("Conversation deleted with response: {msg}"),
+                Err(err) => error!("Got an error while invoking delete_conversation: {err}"),
+            };
+            match invoke::<_, String>("drop_session", &payload).await {
+                Ok(msg) => log!("Dropping the session with response: {msg}"),
+                Err(err) => error!("Got an error while invoking drop_session: {err}"),
+            };
+        });
+    };
+
+    view! { cx,
+        <div class="flex-0 flex flex-col w-48 overflow-scroll border border-gray-700 rounded-lg m-2 p-2">
+            <h2 class="mb-3">"Conversations"</h2>
+            <ul class="flex flex-col gap-1">
+                <For
+                    each=conversations
+                    key=|conversation| conversation.conversation_id.clone()
+                    view=move |cx, conversation: Conversation| {
+                        let id_select = conversation.conversation_id.clone();
+                        let id_active = conversation.conversation_id.clone();
+                        let id_editing = conversation.conversation_id.clone();
+                        let id_rename = conversation.conversation_id.clone();
+                        let id_title = conversation.conversation_id.clone();
+                        let id_delete = conversation.conversation_id.clone();
+                        let title = conversation.title.clone();
+                        view! { cx,
+                            <li class=move || {
+                                format!(
+                                    "flex flex-row items-center gap-1 rounded-lg p-1 {}",
+                                    if conversation_id().0.as_ref() == Some(&id_active) { "bg-base-300" } else { "" }
+                                )
+                            }>
+                                <Show
+                                    when=move || editing().as_ref() == Some(&id_editing)
+                                    fallback=move |cx| {
+                                        let id_select = id_select.clone();
+                                        let title = title.clone();
+                                        view! { cx,
+                                            <button
+                                                class="flex-1 text-left truncate"
+                                                prop:disabled=disabled
+                                                on:click=move |_| on_click_select(id_select.clone())
+                                            >
+                                                {title}
+                                            </button>
+                                        }
+                                    }
+                                >
+                                    <input
+                                        class="flex-1 input input-xs w-full"
+                                        type="text"
+                                        prop:value=conversation.title.clone()
+                                        on:change={
+                                            let id_title = id_title.clone();
+                                            move |ev| on_change_title(id_title.clone(), event_target_value(&ev))
+                                        }
+                                    />
+                                </Show>
+                                <button
+                                    class="btn btn-xs"
+                                    prop:disabled=disabled
+                                    on:click=move |_| set_editing(Some(id_rename.clone()))
+                                >
+                                    <Icon class="h-3 w-3" icon=icon!(AiEditOutlined)/>
+                                </button>
+                                <button
+                                    class="btn btn-xs"
+                                    prop:disabled=disabled
+                                    on:click=move |_| on_click_delete(id_delete.clone())
+                                >
+                                    <Icon class="h-3 w-3" icon=icon!(AiDeleteOutlined)/>
+                                </button>
+                            </li>
+                        }
+                    }
+                />
+            </ul>
+        </div>
+    }
+}
@@ -1,73 +1,24 @@
 use std::path::PathBuf;
 
-use chrono::Local;
+use chrono::{Local, TimeZone};
 use futures::StreamExt;
 use leptos::*;
 use leptos_meta::*;
-use serde::{Deserialize, Serialize};
-use strum::EnumString;
-use tauri_sys::{
-    dialog,
-    event::listen,
-    tauri::{self, invoke},
-};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tauri_sys::{dialog, event::listen};
+use wasm_bindgen::prelude::*;
 
 pub mod components;
 pub mod pages;
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct PayloadModelParams {
-    pub params: ModelParams,
-}
-
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct ModelParams {
-    pub model_params: ModelParameters,
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-pub struct RoPEOverrides {
-    pub frequency_scale: f32,
-    pub frequency_base: usize,
-}
-impl Default for RoPEOverrides {
-    fn default() -> Self {
-        Self {
-            frequency_scale: 1.0,
-            frequency_base: 10_000,
-        }
-    }
-}
-
-#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
-pub struct ModelParameters {
-    pub prefer_mmap: bool,
-    pub context_size: usize,
-    pub lora_adapters: Option<Vec<PathBuf>>,
-    pub use_gpu: bool,
-    pub gpu_layers: Option<usize>,
-    pub rope_overrides: Option<RoPEOverrides>
-}
-
-impl Default for ModelParameters {
-    fn default() -> Self {
-        Self {
-            prefer_mmap: true,
-            context_size: 2048,
-            lora_adapters: None,
-            use_gpu: false,
-            gpu_layers: None,
-            rope_overrides: None
-        }
-    }
-}
-
-#[derive(Clone, PartialEq, Debug, Default)]
-pub enum Entity {
-    #[default]
-    User,
-    Bot,
-}
+pub use personal_assistant_types::{
+    AppError, BenchmarkConfig, BenchmarkProgress, BenchmarkResult, ChatTurn, Conversation, Entity,
+    ExportFormat, InferenceRecord, InferenceStats, InferenceSummary, LoadProgress, LoraMetadata,
+    ModelArchitecture, ModelConfig, ModelDirectory, ModelMetadata, ModelParameters, ModelParams,
+    PayloadModelConfig, PayloadModelParams, PromptTemplate, RoPEOverrides, SamplingParameters,
+    ScanReport, StoredMessage, TokenCount, TokenizerSource, TopPTopK, DEFAULT_LAYOUT,
+    DEFAULT_TEMPLATE, DEFAULT_TRAINED_CONTEXT,
+};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Message {
@@ -75,20 +26,56 @@ pub struct Message {
     pub entity: Entity,
     pub is_loading: bool,
     pub time: String,
+    pub timestamp: i64,
+    pub model_name: Option<String>,
+    pub stats: Option<InferenceStats>,
 }
 
 impl Default for Message {
     fn default() -> Self {
+        let now = Local::now();
         Self {
             content: Default::default(),
             entity: Default::default(),
             is_loading: Default::default(),
-            time: Local::now().format("%a %e %b %Y, %T").to_string(),
+            time: now.format("%a %e %b %Y, %T").to_string(),
+            timestamp: now.timestamp_millis(),
+            model_name: None,
+            stats: None,
+        }
+    }
+}
+
+impl From<StoredMessage> for Message {
+    fn from(message: StoredMessage) -> Self {
+        Self {
+            content: message.content,
+            entity: message.entity,
+            is_loading: false,
+            time: Local
+                .timestamp_millis_opt(message.timestamp)
+                .single()
+                .map(|time| time.format("%a %e %b %Y, %T").to_string())
+                .unwrap_or_default(),
+            timestamp: message.timestamp,
+            model_name: message.model_name,
+            stats: message.stats,
         }
     }
 }
 
 impl Message {
+    pub fn to_stored(&self, conversation_id: String, position: usize) -> StoredMessage {
+        StoredMessage {
+            conversation_id,
+            position,
+            content: self.content.clone(),
+            entity: self.entity,
+            timestamp: self.timestamp,
+            model_name: self.model_name.clone(),
+            stats: self.stats,
+        }
+    }
     pub fn update_content(&mut self, content: &str) {
         self.content.push_str(content);
     }
@@ -99,106 +86,245 @@ impl Message {
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Payload {
-    pub message: String,
+    #[serde(rename(serialize = "conversationId"))]
+    pub conversation_id: String,
+    pub history: Vec<ChatTurn>,
+    pub prompt: String,
+    pub template: PromptTemplate,
+    pub sampling: SamplingParameters,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PayloadCountTokens {
+    pub history: Vec<ChatTurn>,
+    pub prompt: String,
+    pub template: PromptTemplate,
+    pub sampling: SamplingParameters,
+}
+
+impl From<&Message> for ChatTurn {
+    fn from(message: &Message) -> Self {
+        Self {
+            entity: message.entity,
+            content: message.content.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct PayloadId {
+pub struct PayloadConversationId {
+    #[serde(rename(serialize = "conversationId"))]
+    pub conversation_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadTitle {
+    pub title: String,
+    #[serde(rename(serialize = "templateName"))]
+    pub template_name: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadConversationTemplate {
+    #[serde(rename(serialize = "conversationId"))]
+    pub conversation_id: String,
+    #[serde(rename(serialize = "templateName"))]
+    pub template_name: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadModelConfigTemplate {
     pub name: String,
+    #[serde(rename(serialize = "templateName"))]
+    pub template_name: Option<String>,
+}
+
+/// Fields of a model config to change, the missing ones are kept
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ModelConfigPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_architecture: Option<ModelArchitecture>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokenizer_source: Option<TokenizerSource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_params: Option<ModelParameters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingParameters>,
+}
+
+impl ModelConfigPatch {
+    /// Fields of `edited` that differ from `original`
+    pub fn diff(original: &ModelConfig, edited: &ModelConfig) -> Self {
+        Self {
+            model_path: (edited.model_path != original.model_path).then(|| edited.model_path.clone()),
+            model_architecture: (edited.model_architecture != original.model_architecture)
+                .then_some(edited.model_architecture),
+            tokenizer_source: (edited.tokenizer_source != original.tokenizer_source)
+                .then(|| edited.tokenizer_source.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct PayloadModelConfig {
-    #[serde(rename(serialize = "modelConfig"))]
-    pub model_config: ModelConfig,
+pub struct PayloadModelConfigPatch {
+    pub name: String,
+    pub patch: ModelConfigPatch,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, EnumString, Default, Eq, Hash, PartialEq, Copy)]
-pub enum ModelArchitecture {
-    Bloom,
-    Gpt2,
-    GptJ,
-    GptNeoX,
-    #[default]
-    Llama,
-    Mpt,
-    Falcon,
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadRename {
+    pub name: String,
+    #[serde(rename(serialize = "newName"))]
+    pub new_name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, EnumString, Default, PartialEq)]
-pub enum TokenizerSource {
-    #[default]
-    Embedded,
-    HuggingFaceTokenizerFile(PathBuf),
-    HuggingFaceRemote(String),
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadTemplate {
+    pub template: PromptTemplate,
+}
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadRenameConversation {
+    #[serde(rename(serialize = "conversationId"))]
+    pub conversation_id: String,
+    pub title: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadMessage {
+    pub message: StoredMessage,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
-pub struct ModelConfig {
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadPort {
+    pub port: Option<u16>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadId {
     pub name: String,
-    pub model_architecture: ModelArchitecture,
-    pub model_path: PathBuf,
-    pub tokenizer_source: TokenizerSource,
 }
 
-impl ModelConfig {
-    pub fn new() -> Self {
-        Self::default()
-    }
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadBenchmarkConfig {
+    pub config: BenchmarkConfig,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadBenchmarkId {
+    #[serde(rename(serialize = "benchmarkId"))]
+    pub benchmark_id: String,
+}
+
+/// Benchmarks of the model, or of every model, written to `path`
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadExport {
+    pub path: PathBuf,
+    pub format: ExportFormat,
+    #[serde(rename(serialize = "modelName"))]
+    pub model_name: Option<String>,
+}
+
+/// `None` stands for every model
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadModelName {
+    #[serde(rename(serialize = "modelName"))]
+    pub model_name: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PayloadPath {
+    pub path: PathBuf,
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct ModelConfigState(bool);
 
-#[derive(Deserialize)]
-pub struct InferenceStats {
-    /// How long it took to feed the prompt.
-    pub feed_prompt_duration: std::time::Duration,
-    /// How many tokens the prompt was.
-    pub prompt_tokens: usize,
-    /// How long it took to predict new tokens.
-    pub predict_duration: std::time::Duration,
-    /// The number of predicted tokens.
-    pub predict_tokens: usize,
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"], js_name = invoke, catch)]
+    async fn invoke_js(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+}
+
+/// Invoke a backend command, a rejection is read back as an `AppError`
+pub async fn invoke<A: Serialize, R: DeserializeOwned>(cmd: &str, args: &A) -> Result<R, AppError> {
+    let args = serde_wasm_bindgen::to_value(args).map_err(|err| AppError::Internal {
+        reason: err.to_string(),
+    })?;
+    match invoke_js(cmd, args).await {
+        Ok(value) => serde_wasm_bindgen::from_value(value).map_err(|err| AppError::Internal {
+            reason: err.to_string(),
+        }),
+        // Tauri itself rejects with a plain string (e.g. invalid arguments)
+        Err(err) => Err(serde_wasm_bindgen::from_value(err.clone()).unwrap_or_else(|_| {
+            AppError::Internal {
+                reason: err.as_string().unwrap_or_else(|| format!("{err:?}")),
+            }
+        })),
+    }
 }
-impl std::fmt::Display for InferenceStats {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let Self {
-            feed_prompt_duration,
-            prompt_tokens,
-            predict_duration,
-            predict_tokens,
-        } = *self;
-
-        let feed_prompt_duration = feed_prompt_duration.as_millis();
-        let predict_duration = predict_duration.as_millis();
-        let per_token_duration = if predict_tokens == 0 {
-            0.0
-        } else {
-            predict_duration as f64 / predict_tokens as f64
-        };
 
-        writeln!(f, "feed_prompt_duration: {}ms", feed_prompt_duration)?;
-        writeln!(f, "prompt_tokens: {}", prompt_tokens)?;
-        writeln!(f, "predict_duration: {}ms", predict_duration)?;
-        writeln!(f, "predict_tokens: {}", predict_tokens)?;
-        write!(f, "per_token_duration: {:.3}ms", per_token_duration)
+/// Show the error with its recovery hint, then reconnect the database when it is the cause
+pub async fn show_error(title: &str, err: &AppError) {
+    let message = match err.recovery() {
+        Some(recovery) => format!("{err}\n\n{recovery}"),
+        None => err.to_string(),
+    };
+    if let Err(dialog_err) = dialog::MessageDialogBuilder::new()
+        .set_title(title)
+        .set_kind(dialog::MessageDialogKind::Error)
+        .message(message.as_str())
+        .await
+    {
+        error!("Dialog {title}: {dialog_err}");
+    }
+    if err.needs_reconnect() {
+        match invoke::<_, String>("connect", &()).await {
+            Ok(msg) => log!("Reconnecting to the database: {msg}"),
+            Err(err) => error!("Got an error while invoking connect: {err}"),
+        };
     }
 }
 
+/// Id of the current conversation, `None` until its first message is stored
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct ConversationId(pub Option<String>);
+
 pub fn setup(cx: Scope) {
     let (model_config_loaded, set_model_config_loaded) =
         create_signal(cx, ModelConfigState::default());
     provide_context(cx, (model_config_loaded, set_model_config_loaded));
     let (messages, set_messages) = create_signal(cx, Vec::<Message>::new());
     provide_context(cx, (messages, set_messages));
+    let (conversation_id, set_conversation_id) = create_signal(cx, ConversationId::default());
+    provide_context(cx, (conversation_id, set_conversation_id));
     let (is_model_connected, set_is_model_connected) = create_signal(cx, false);
     provide_context(cx, (is_model_connected, set_is_model_connected));
+    let (load_progress, set_load_progress) = create_signal(cx, None::<LoadProgress>);
+    provide_context(cx, (load_progress, set_load_progress));
+    let (benchmark_progress, set_benchmark_progress) = create_signal(cx, None::<BenchmarkProgress>);
+    provide_context(cx, (benchmark_progress, set_benchmark_progress));
     let (model_params, set_model_params) = create_signal(cx, ModelParameters::default());
     provide_context(cx, (model_params, set_model_params));
+    let (sampling_params, set_sampling_params) =
+        create_signal(cx, SamplingParameters::default());
+    provide_context(cx, (sampling_params, set_sampling_params));
     let (model_configs, set_model_configs) = create_signal(cx, Vec::<ModelConfig>::new());
     // let (models, set_models) = create_signal(cx, vec![ModelConfig::default(); 10]);
     provide_context(cx, (model_configs, set_model_configs));
+    let (conversations, set_conversations) = create_signal(cx, Vec::<Conversation>::new());
+    provide_context(cx, (conversations, set_conversations));
+    let (prompt_templates, set_prompt_templates) = create_signal(cx, Vec::<PromptTemplate>::new());
+    provide_context(cx, (prompt_templates, set_prompt_templates));
+    // Model config picked in the settings
+    let (loaded_model_config, set_loaded_model_config) = create_signal(cx, None::<ModelConfig>);
+    provide_context(cx, (loaded_model_config, set_loaded_model_config));
 
     // Start listening
     spawn_local(async move {
@@ -222,12 +348,44 @@ pub fn setup(cx: Scope) {
         }
     });
 
+    // Listen for the model loading progress
+    spawn_local(async move {
+        match listen::<LoadProgress>("model_load_progress").await {
+            Ok(mut events) => {
+                while let Some(event) = events.next().await {
+                    set_load_progress(Some(event.payload));
+                }
+                debug_warn!("Stopped listening");
+                warn!("Stopped listening");
+            }
+            Err(err) => {
+                error!("Listen external got an error: {err}")
+            }
+        }
+    });
+
+    // Listen for the benchmark progress
+    spawn_local(async move {
+        match listen::<BenchmarkProgress>("benchmark_progress").await {
+            Ok(mut events) => {
+                while let Some(event) = events.next().await {
+                    set_benchmark_progress(Some(event.payload));
+                }
+                debug_warn!("Stopped listening");
+                warn!("Stopped listening");
+            }
+            Err(err) => {
+                error!("Listen external got an error: {err}")
+            }
+        }
+    });
+
     // Init the database listening
     spawn_local(async move {
         log!("Init the database");
         match invoke::<(), String>("connect", &()).await {
             Ok(msg) => log!("DB init {msg}"),
-            Err(err) => log!("DB init {err:#?}"),
+            Err(err) => log!("DB init {err}"),
         }
     });
     // Listen for database changes
@@ -236,21 +394,42 @@ pub fn setup(cx: Scope) {
         match listen::<()>("db_sync_event").await {
             Ok(mut events) => {
                 while events.next().await.is_some() {
-                    match tauri::invoke::<_, Vec<ModelConfig>>("get_model_configs", &()).await {
+                    match invoke::<_, Vec<ModelConfig>>("get_model_configs", &()).await {
                         Ok(model_configs) => {
+                            // Keep the loaded model config up to date
+                            set_loaded_model_config.update(|loaded_model_config| {
+                                if let Some(loaded_model_config) = loaded_model_config {
+                                    if let Some(updated) = model_configs
+                                        .iter()
+                                        .find(|model_config| model_config.name == loaded_model_config.name)
+                                    {
+                                        *loaded_model_config = updated.clone();
+                                    }
+                                }
+                            });
                             set_model_configs(model_configs);
                         }
-                        Err(err) => {
-                            match dialog::MessageDialogBuilder::new()
-                                .set_title("Get model configs (Sync)")
-                                .set_kind(dialog::MessageDialogKind::Error)
-                                .message(format!("{err:#?}").as_str())
-                                .await
-                            {
-                                Ok(()) => (),
-                                Err(err) => error!("Dialog error (sync) get model configs: {err}"),
-                            };
-                        }
+                        Err(err) => show_error("Get model configs (Sync)", &err).await,
+                    };
+                }
+                debug_warn!("Stopped listening");
+                warn!("Stopped listening");
+            }
+            Err(err) => {
+                error!("Listen external got an error: {err}")
+            }
+        }
+    });
+
+    // Listen for conversation changes
+    spawn_local(async move {
+        log!("Conversations sync");
+        match listen::<()>("conversation_sync_event").await {
+            Ok(mut events) => {
+                while events.next().await.is_some() {
+                    match invoke::<_, Vec<Conversation>>("list_conversations", &()).await {
+                        Ok(conversations) => set_conversations(conversations),
+                        Err(err) => error!("Got an error while invoking list_conversations: {err}"),
                     };
                 }
                 debug_warn!("Stopped listening");
@@ -262,23 +441,49 @@ pub fn setup(cx: Scope) {
         }
     });
 
+    // Listen for prompt template changes
+    spawn_local(async move {
+        log!("Prompt templates sync");
+        match listen::<()>("template_sync_event").await {
+            Ok(mut events) => {
+                while events.next().await.is_some() {
+                    match invoke::<_, Vec<PromptTemplate>>("list_prompt_templates", &()).await {
+                        Ok(templates) => set_prompt_templates(templates),
+                        Err(err) => error!("Got an error while invoking list_prompt_templates: {err}"),
+                    };
+                }
+                debug_warn!("Stopped listening");
+                warn!("Stopped listening");
+            }
+            Err(err) => {
+                error!("Listen external got an error: {err}")
+            }
+        }
+    });
+
+    // Get the prompt templates
+    spawn_local(async move {
+        match invoke::<_, Vec<PromptTemplate>>("list_prompt_templates", &()).await {
+            Ok(templates) => set_prompt_templates(templates),
+            Err(err) => error!("Got an error while invoking list_prompt_templates: {err}"),
+        };
+    });
+
+    // Get the stored conversations
+    spawn_local(async move {
+        match invoke::<_, Vec<Conversation>>("list_conversations", &()).await {
+            Ok(conversations) => set_conversations(conversations),
+            Err(err) => error!("Got an error while invoking list_conversations: {err}"),
+        };
+    });
+
     // Get local model configs
     spawn_local(async move {
-        match tauri::invoke::<_, Vec<ModelConfig>>("get_model_configs", &()).await {
+        match invoke::<_, Vec<ModelConfig>>("get_model_configs", &()).await {
             Ok(model_configs) => {
                 set_model_configs(model_configs);
             }
-            Err(err) => {
-                match dialog::MessageDialogBuilder::new()
-                    .set_title("Get model configs")
-                    .set_kind(dialog::MessageDialogKind::Error)
-                    .message(format!("{err:#?}").as_str())
-                    .await
-                {
-                    Ok(()) => (),
-                    Err(err) => error!("Dialog error get model configs: {err}"),
-                };
-            }
+            Err(err) => show_error("Get model configs", &err).await,
         };
     });
 
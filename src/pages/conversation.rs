@@ -1,7 +1,20 @@
-use crate::{components::Chat, Entity, Message, Payload, InferenceStats};
+use crate::{
+    components::{Chat, SideBar},
+    invoke, show_error, ChatTurn, Conversation, ConversationId, Entity, InferenceStats, Message, ModelConfig,
+    Payload, PayloadConversationId, PayloadConversationTemplate, PayloadCountTokens, PayloadMessage,
+    PayloadTitle, PromptTemplate, SamplingParameters, StoredMessage, TokenCount, DEFAULT_TEMPLATE,
+};
 use leptos::*;
 use leptos_icons::*;
-use tauri_sys::{tauri, dialog};
+use tauri_sys::dialog;
+
+/// Store a message of the conversation, failures are only logged to keep the chat usable
+async fn store_message(message: StoredMessage) {
+    match invoke::<_, String>("add_message", &PayloadMessage { message }).await {
+        Ok(msg) => log!("Storing the message with response: {msg}"),
+        Err(err) => error!("Got an error while invoking add_message: {err}"),
+    };
+}
 
 #[component]
 pub fn Conversation(cx: Scope) -> impl IntoView {
@@ -10,26 +23,152 @@ pub fn Conversation(cx: Scope) -> impl IntoView {
     let (messages, set_messages) =
         use_context::<(ReadSignal<Vec<Message>>, WriteSignal<Vec<Message>>)>(cx)
             .expect("to have found the setter and getter provided for messages");
+    let (conversation_id, set_conversation_id) =
+        use_context::<(ReadSignal<ConversationId>, WriteSignal<ConversationId>)>(cx)
+            .expect("to have found the setter and getter provided for the conversation id");
+    let (sampling_params, _) =
+        use_context::<(ReadSignal<SamplingParameters>, WriteSignal<SamplingParameters>)>(cx)
+            .expect("to have found the setter and getter provided for sampling parameters");
+    let (conversations, _) =
+        use_context::<(ReadSignal<Vec<Conversation>>, WriteSignal<Vec<Conversation>>)>(cx)
+            .expect("to have found the setter and getter provided for conversations");
+    let (prompt_templates, _) =
+        use_context::<(ReadSignal<Vec<PromptTemplate>>, WriteSignal<Vec<PromptTemplate>>)>(cx)
+            .expect("to have found the setter and getter provided for prompt templates");
+    let (loaded_model_config, _) =
+        use_context::<(ReadSignal<Option<ModelConfig>>, WriteSignal<Option<ModelConfig>>)>(cx)
+            .expect("to have found the setter and getter provided for the loaded model config");
     let (user_input, set_user_input) = create_signal(cx, String::new());
-    let (is_valid_template, set_is_valid_template) = create_signal(cx, true);
     let (is_model_predicting, set_is_model_predicting) = create_signal(cx, false);
-    let (prompt, set_prompt) = create_signal(cx, r#"A chat between a human ("User") and an AI assistant ("Assistant"). The assistant gives helpful, detailed, and polite answers to the human's questions.
-Assistant: How may I help you?
-User: {{PROMPT}}
-Assistant: "#.to_string());
+    // Template picked for this conversation, the model config one is used otherwise
+    let (conversation_template, set_conversation_template) = create_signal(cx, None::<String>);
 
+    // Follow the template stored with the selected conversation
     create_effect(cx, move |_| {
-        set_is_valid_template(prompt().contains("{{PROMPT}}"));
+        if let Some(current_conversation_id) = conversation_id().0 {
+            let template_name = conversations.with(|conversations| {
+                conversations
+                    .iter()
+                    .find(|conversation| conversation.conversation_id == current_conversation_id)
+                    .and_then(|conversation| conversation.template_name.clone())
+            });
+            set_conversation_template(template_name);
+        }
     });
 
-    let on_submit = move |ev: leptos::ev::SubmitEvent| {
-        ev.prevent_default();
-        set_messages.update(|messages| {
-            messages.push(Message {
-                content: user_input(),
-                ..Default::default()
+    let template_name = move || {
+        conversation_template()
+            .or_else(|| loaded_model_config().and_then(|model_config| model_config.template_name))
+            .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string())
+    };
+    let template = move || {
+        let template_name = template_name();
+        prompt_templates.with(|templates| {
+            templates
+                .iter()
+                .find(|template| template.name == template_name)
+                .cloned()
+        })
+    };
+
+    // Size of the conversation with the message being typed, counted by the loaded model
+    let token_count = create_local_resource(
+        cx,
+        move || {
+            if !is_model_connected() || is_model_predicting() {
+                return None;
+            }
+            Some(PayloadCountTokens {
+                history: messages.with(|messages| messages.iter().map(ChatTurn::from).collect()),
+                prompt: user_input(),
+                template: template()?,
+                sampling: sampling_params(),
             })
+        },
+        |payload| async move {
+            match invoke::<_, TokenCount>("count_tokens", &payload?).await {
+                Ok(token_count) => Some(token_count),
+                Err(err) => {
+                    warn!("Got an error while invoking count_tokens: {err}");
+                    None
+                }
+            }
+        },
+    );
+    let overflows = move || {
+        token_count
+            .read(cx)
+            .flatten()
+            .map(|token_count| token_count.overflows())
+            .unwrap_or_default()
+    };
+
+    // The kept session was fed with the previous template
+    let on_change_template = move |ev| {
+        let template_name = Some(event_target_value(&ev)).filter(|name| !name.is_empty());
+        set_conversation_template(template_name.clone());
+        let Some(current_conversation_id) = conversation_id().0 else {
+            return;
+        };
+        spawn_local(async move {
+            let payload = PayloadConversationTemplate {
+                conversation_id: current_conversation_id.clone(),
+                template_name,
+            };
+            match invoke::<_, Conversation>("set_conversation_template", &payload).await {
+                Ok(conversation) => log!("Conversation template set: {conversation:#?}"),
+                Err(err) => show_error("Prompt template", &err).await,
+            };
+            let payload = PayloadConversationId {
+                conversation_id: current_conversation_id,
+            };
+            match invoke::<_, String>("reset_session", &payload).await {
+                Ok(msg) => log!("Resetting the session with response: {msg}"),
+                Err(err) => warn!("Got an error while invoking reset_session: {err}"),
+            };
+        });
+    };
+
+    let on_click_new_chat = move |ev: leptos::ev::MouseEvent| {
+        ev.prevent_default();
+        set_messages.update(|messages| messages.clear());
+        set_conversation_template(None);
+        let Some(current_conversation_id) = conversation_id().0 else {
+            return;
+        };
+        set_conversation_id(ConversationId::default());
+        let payload = PayloadConversationId {
+            conversation_id: current_conversation_id,
+        };
+        spawn_local(async move {
+            match invoke::<_, String>("drop_session", &payload).await {
+                Ok(msg) => log!("Dropping the session with response: {msg}"),
+                Err(err) => error!("Got an error while invoking drop_session: {err}"),
+            };
+        });
+    };
+
+    let on_click_stop = move |ev: leptos::ev::MouseEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            match invoke::<_, String>("stop_prediction", &()).await {
+                Ok(msg) => log!("Stopping the prediction with response: {msg}"),
+                Err(err) => error!("Got an error while invoking stop_prediction: {err}"),
+            };
         });
+    };
+
+    let send = move || {
+        let Some(template) = template() else {
+            return;
+        };
+        let user_message = Message {
+            content: user_input(),
+            ..Default::default()
+        };
+        let position = messages.with(Vec::len);
+        let history = messages.with(|messages| messages.iter().map(ChatTurn::from).collect::<Vec<_>>());
+        set_messages.update(|messages| messages.push(user_message.clone()));
         set_messages.update(|messages| {
             messages.push(Message {
                 content: "".into(),
@@ -39,96 +178,210 @@ Assistant: "#.to_string());
             })
         });
         set_is_model_predicting.set(true);
-        let payload = Payload {
-            message: prompt().replace("{{PROMPT}}", user_input().as_ref()),
-        };
-        log!("Payload\n{payload:#?}");
+        let prompt = user_input();
+        let template_name = conversation_template();
+        let sampling = sampling_params();
+        let title = user_input().chars().take(32).collect::<String>();
         set_user_input.update(|user_input| user_input.clear());
 
         spawn_local(async move {
-            // match tauri::invoke::<_, InferenceStats>("predict_simulated", &payload).await {
-            match tauri::invoke::<_, InferenceStats>("predict", &payload).await {
+            // The conversation is stored with its first message
+            let current_conversation_id = match conversation_id().0 {
+                Some(current_conversation_id) => current_conversation_id,
+                None => match invoke::<_, Conversation>("create_conversation", &PayloadTitle { title, template_name }).await {
+                    Ok(conversation) => {
+                        set_conversation_id(ConversationId(Some(conversation.conversation_id.clone())));
+                        conversation.conversation_id
+                    }
+                    Err(err) => {
+                        error!("Got an error while invoking create_conversation: {err}");
+                        format!("unsaved-{}", user_message.timestamp)
+                    }
+                },
+            };
+            store_message(user_message.to_stored(current_conversation_id.clone(), position)).await;
+            let payload = Payload {
+                conversation_id: current_conversation_id.clone(),
+                history,
+                prompt,
+                template,
+                sampling,
+            };
+            log!("Payload\n{payload:#?}");
+            match invoke::<_, InferenceStats>("predict", &payload).await {
                 Ok(stats) => {
-                    set_messages.update(|messages| messages.last_mut().unwrap().done());
+                    set_messages.update(|messages| {
+                        let bot_message = messages.last_mut().unwrap();
+                        bot_message.done();
+                        bot_message.stats = Some(stats);
+                    });
                     set_is_model_predicting.set(false);
-                        match dialog::MessageDialogBuilder::new()
-                            .set_title("Model prediction")
-                            .set_kind(dialog::MessageDialogKind::Info)
-                            .message(format!("{stats}").as_str())
-                            .await
-                        {
-                            Ok(()) => (),
-                            Err(err) => error!("Dialog info model prediction: {err}"),
-                        };
+                    if let Some(bot_message) = messages.with(|messages| messages.last().cloned()) {
+                        store_message(bot_message.to_stored(current_conversation_id, position + 1)).await;
+                    }
                 },
                 Err(err) => {
                     set_messages.update(|messages| messages.last_mut().unwrap().done());
                     set_is_model_predicting.set(false);
-                    match dialog::MessageDialogBuilder::new()
-                        .set_title("Model prediction")
-                        .set_kind(dialog::MessageDialogKind::Error)
-                        .message(format!("{err:#?}").as_str())
-                        .await
-                    {
-                        Ok(()) => (),
-                        Err(err) => error!("Dialog error model prediction: {err}"),
-                    };
+                    show_error("Model prediction", &err).await;
                 },
             };
         });
     };
 
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        if !overflows() {
+            send();
+            return;
+        }
+        spawn_local(async move {
+            match dialog::MessageDialogBuilder::new()
+                .set_title("Context size")
+                .set_kind(dialog::MessageDialogKind::Warning)
+                .confirm("The conversation does not fit in the context, the oldest messages will be forgotten. Send anyway ?")
+                .await
+            {
+                Ok(true) => send(),
+                Ok(false) => (),
+                Err(err) => error!("Dialog warning context size: {err}"),
+            };
+        });
+    };
+
     view! { cx,
-        // Prompt template
-        <div class="flex-0 flex flex-col items-start border border-gray-700 rounded-lg m-2 p-2">
-            <h2 class="mb-3">"Template"</h2>
-            <textarea
-                placeholder="Model Template"
-                rows="4"
-                prop:value=prompt
-                on:change=move |ev| set_prompt(event_target_value(&ev))
-                class=move || format!("textarea {} textarea-md w-full h-full", if is_valid_template() {"textarea-bordered"} else {"textarea-error"})
-            ></textarea>
-              <Show
-                when=move || { !is_valid_template() }
-                fallback=|cx| view! { cx, <p class="text-green-500 text-xs mx-2">"Valid template !"</p> }
-              >
-                <p class="text-red-500 text-xs mx-2">"The template should contain "<br>"{{PROMPT}}"</br>" which will be use to inject the user message !"</p>
-              </Show>
-        </div>
-        // Conversation area"
-        // There is a bug with using both justify-end and overflow-scroll
-        <div class="flex-1 flex flex-col justify-end overflow-scroll border border-gray-700 rounded-lg m-2 p-2">
+        <div class="flex-1 flex flex-row overflow-hidden">
+            // Stored conversations
+            <SideBar disabled=is_model_predicting/>
+            <div class="flex-1 flex flex-col overflow-hidden">
+            // Prompt template
+            <div class="flex-0 flex flex-col items-start border border-gray-700 rounded-lg m-2 p-2">
+                <div class="flex w-full justify-between mb-3">
+                    <h2>"Template"</h2>
+                    <button
+                        class="btn btn-sm"
+                        prop:disabled=is_model_predicting
+                        on:click=on_click_new_chat
+                    >
+                        "New chat"
+                    </button>
+                </div>
+                <select
+                    class="select select-bordered select-sm w-full"
+                    prop:disabled=is_model_predicting
+                    on:change=on_change_template
+                >
+                    <option value="" selected=move || conversation_template().is_none()>
+                        {move || {
+                            let model_template = loaded_model_config()
+                                .and_then(|model_config| model_config.template_name)
+                                .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+                            format!("Model default ({model_template})")
+                        }}
+                    </option>
+                    <For
+                        each=prompt_templates
+                        key=|template| template.name.clone()
+                        view=move |cx, template: PromptTemplate| {
+                            let name = template.name.clone();
+                            view! { cx,
+                                <option
+                                    value=template.name.clone()
+                                    selected=move || conversation_template().as_ref() == Some(&name)
+                                >
+                                    {template.name.clone()}
+                                </option>
+                            }
+                        }
+                    />
+                </select>
+                <Show
+                    when=move || template().is_some()
+                    fallback=|cx| view! { cx, <p class="text-red-500 text-xs mx-2">"The prompt template was not found, pick another one !"</p> }
+                >
+                    <pre class="text-xs mx-2 mt-2 whitespace-pre-wrap opacity-70">
+                        {move || template().map(|template| template.preview()).unwrap_or_default()}
+                    </pre>
+                </Show>
+            </div>
+            // Conversation area"
+            // There is a bug with using both justify-end and overflow-scroll
+            <div class="flex-1 flex flex-col justify-end overflow-scroll border border-gray-700 rounded-lg m-2 p-2">
+                {move || {
+                    messages()
+                        .into_iter()
+                        .map(|message| {
+                            view! { cx, <Chat message=message/> }
+                        })
+                        .collect_view(cx)
+                }}
+            </div>
+            // Context usage
             {move || {
-                messages()
-                    .into_iter()
-                    .map(|message| {
-                        view! { cx, <Chat message=message/> }
+                token_count
+                    .read(cx)
+                    .flatten()
+                    .map(|token_count| {
+                        view! { cx,
+                            <div class="flex-0 flex flex-row items-center gap-4 mx-4">
+                                <progress
+                                    class="progress flex-1"
+                                    class:progress-success=!token_count.overflows()
+                                    class:progress-error=token_count.overflows()
+                                    prop:value=token_count.used + token_count.reserved
+                                    prop:max=token_count.context_size
+                                ></progress>
+                                <span class="text-xs whitespace-nowrap" title="Prompt and history, plus the tokens kept for the answer">
+                                    {format!(
+                                        "{} + {} / {} tokens",
+                                        token_count.used,
+                                        token_count.reserved,
+                                        token_count.context_size,
+                                    )}
+                                </span>
+                                <Show when=move || token_count.overflows() fallback=|_| ()>
+                                    <span class="text-xs text-red-500">"The oldest messages will be forgotten"</span>
+                                </Show>
+                            </div>
+                        }
                     })
-                    .collect_view(cx)
             }}
-        </div>
-        // User input area
-        <div class="flex-0">
-            <form
-                class="flex flex-row items-center border border-gray-700 rounded-lg m-2 p-2"
-                on:submit=on_submit
-            >
-                <input
-                    class="flex-1 input w-full mx-2"
-                    placeholder="Say something to the AI !"
-                    type="text"
-                    prop:value=user_input
-                    on:input=move |ev| set_user_input(event_target_value(&ev))
-                />
-                <button
-                    type="submit"
-                    prop:disabled=move || user_input.with(String::is_empty) | !is_valid_template() | !is_model_connected() | is_model_predicting()
-                    class="btn flex-0 mx-2"
+            // User input area
+            <div class="flex-0">
+                <form
+                    class="flex flex-row items-center border border-gray-700 rounded-lg m-2 p-2"
+                    on:submit=on_submit
                 >
-                    <Icon class="h-5 w-5" icon=icon!(BsSendFill)/>
-                </button>
-            </form>
+                    <input
+                        class="flex-1 input w-full mx-2"
+                        placeholder="Say something to the AI !"
+                        type="text"
+                        prop:value=user_input
+                        on:input=move |ev| set_user_input(event_target_value(&ev))
+                    />
+                    <Show
+                        when=is_model_predicting
+                        fallback=move |cx| view! { cx,
+                            <button
+                                type="submit"
+                                prop:disabled=move || user_input.with(String::is_empty) | template().is_none() | !is_model_connected()
+                                class="btn flex-0 mx-2"
+                            >
+                                <Icon class="h-5 w-5" icon=icon!(BsSendFill)/>
+                            </button>
+                        }
+                    >
+                        <button
+                            type="button"
+                            class="btn btn-error flex-0 mx-2"
+                            on:click=on_click_stop
+                        >
+                            <Icon class="h-5 w-5" icon=icon!(BsStopFill)/>
+                        </button>
+                    </Show>
+                </form>
+            </div>
+            </div>
         </div>
     }
 }
@@ -1,6 +1,8 @@
 mod conversation;
 mod setting;
+mod stats;
 
 
 pub use conversation::Conversation;
 pub use setting::Setting;
+pub use stats::Stats;
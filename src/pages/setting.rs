@@ -3,9 +3,9 @@ use std::str::FromStr;
 
 use leptos::*;
 use leptos_icons::*;
-use tauri_sys::{dialog, tauri};
+use tauri_sys::dialog;
 
-use crate::{ModelArchitecture, ModelConfig, ModelConfigState, PayloadModelConfig, PayloadId, ModelParameters, PayloadModelParams, ModelParams};
+use crate::{invoke, show_error, AppError, LoadProgress, ModelArchitecture, ModelConfig, ModelConfigState, LoraMetadata, ModelMetadata, ModelConfigPatch, ModelDirectory, PayloadModelConfig, PayloadModelConfigPatch, PayloadPath, PayloadRename, ScanReport, PayloadId, ModelParameters, PayloadModelParams, ModelParams, SamplingParameters, PayloadPort, PayloadModelConfigTemplate, PayloadTemplate, PromptTemplate, RoPEOverrides, TokenizerSource, DEFAULT_TRAINED_CONTEXT};
 
 // FIXME: We are not using correctly the signals there is some signals that might not be working as
 // intended
@@ -21,24 +21,51 @@ pub fn Setting(cx: Scope) -> impl IntoView {
     let (model_configs, _set_model_configs) =
         use_context::<(ReadSignal<Vec<ModelConfig>>, WriteSignal<Vec<ModelConfig>>)>(cx)
             .expect("to have found the setter and getter provided for model config state");
-    let (model_params, _) =
+    let (model_params, set_model_params) =
     use_context::<(ReadSignal<ModelParameters>, WriteSignal<ModelParameters>)>(cx)
         .expect("to have found the setter and getter provided for model status");
+    let (_, set_sampling_params) =
+        use_context::<(ReadSignal<SamplingParameters>, WriteSignal<SamplingParameters>)>(cx)
+            .expect("to have found the setter and getter provided for sampling parameters");
+    let (load_progress, set_load_progress) =
+        use_context::<(ReadSignal<Option<LoadProgress>>, WriteSignal<Option<LoadProgress>>)>(cx)
+            .expect("to have found the setter and getter provided for the model loading progress");
+    let (is_model_loading, set_is_model_loading) = create_signal(cx, false);
+    let (prompt_templates, _) =
+        use_context::<(ReadSignal<Vec<PromptTemplate>>, WriteSignal<Vec<PromptTemplate>>)>(cx)
+            .expect("to have found the setter and getter provided for prompt templates");
+    let (_, set_loaded_model_config) =
+        use_context::<(ReadSignal<Option<ModelConfig>>, WriteSignal<Option<ModelConfig>>)>(cx)
+            .expect("to have found the setter and getter provided for the loaded model config");
     let (model_config, set_model_config) = create_signal(cx, ModelConfig::default());
     let (model_file_path, set_model_file_path) = create_signal(cx, String::new());
+    // Header of the picked model file, or why it can not be loaded
+    let (model_metadata, set_model_metadata) = create_signal(cx, None::<Result<ModelMetadata, AppError>>);
+    let is_model_file_invalid = move || matches!(model_metadata(), Some(Err(_)));
+    // Architectures the backend was built with
+    let (architectures, set_architectures) = create_signal(cx, Vec::<ModelArchitecture>::new());
+    spawn_local(async move {
+        match invoke::<_, Vec<ModelArchitecture>>("list_supported_architectures", &()).await {
+            Ok(supported) => set_architectures(supported),
+            Err(err) => show_error("Supported architectures", &err).await,
+        };
+    });
 
     // Setup the on_click functions
     let on_click_load_unload_model = move |ev| {
         log!("on_click_load_unload_model: {ev:#?}");
         if event_target_checked(&ev) {
+            set_load_progress(None);
+            set_is_model_loading(true);
             spawn_local(async move {
                 let model_params_payload = PayloadModelParams{
                     params: ModelParams {
                         model_params: model_params()
                     }
                 };
-                // match tauri::invoke::<_, String>("load_dynamic_model_simulated", &()).await {
-                match tauri::invoke::<PayloadModelParams, String>("load_dynamic_model", &model_params_payload).await {
+                let result = invoke::<PayloadModelParams, String>("load_dynamic_model", &model_params_payload).await;
+                set_is_model_loading(false);
+                match result {
                     Ok(msg) => {
                         set_is_model_connected
                             .update(|is_model_connected| *is_model_connected = true);
@@ -52,25 +79,21 @@ pub fn Setting(cx: Scope) -> impl IntoView {
                             Err(err) => error!("Dialog model loading: {err}"),
                         };
                     }
+                    Err(AppError::LoadAborted) => {
+                        set_is_model_connected
+                            .update(|is_model_connected| *is_model_connected = false);
+                        log!("Model loading aborted");
+                    }
                     Err(err) => {
                         set_is_model_connected
                             .update(|is_model_connected| *is_model_connected = false);
-                        match dialog::MessageDialogBuilder::new()
-                            .set_title("invoking load_dynamic_model")
-                            .set_kind(dialog::MessageDialogKind::Error)
-                            .message(format!("{err}").as_str())
-                            .await
-                        {
-                            Ok(()) => (),
-                            Err(err) => error!("Dialog model loading: {err}"),
-                        };
+                        show_error("Model loading", &err).await;
                     }
                 };
             });
         } else {
             spawn_local(async move {
-                // match tauri::invoke::<_, String>("unload_dynamic_model_simulated", &()).await {
-                match tauri::invoke::<_, String>("unload_dynamic_model", &()).await {
+                match invoke::<_, String>("unload_dynamic_model", &()).await {
                     Ok(msg) => {
                         set_is_model_connected
                             .update(|is_model_connected| *is_model_connected = false);
@@ -87,21 +110,24 @@ pub fn Setting(cx: Scope) -> impl IntoView {
                     Err(err) => {
                         set_is_model_connected
                             .update(|is_model_connected| *is_model_connected = true);
-                        match dialog::MessageDialogBuilder::new()
-                            .set_title("Model unloading")
-                            .set_kind(dialog::MessageDialogKind::Error)
-                            .message(format!("{err:#?}").as_str())
-                            .await
-                        {
-                            Ok(()) => (),
-                            Err(err) => error!("Dialog error model unloading: {err}"),
-                        };
+                        show_error("Model unloading", &err).await;
                     }
                 };
             });
         }
     };
 
+    let on_click_abort_model_load = move |ev: leptos::ev::MouseEvent| {
+        log!("on_click_abort_model_load: {ev:#?}");
+        ev.prevent_default();
+        spawn_local(async move {
+            match invoke::<_, String>("abort_model_load", &()).await {
+                Ok(msg) => log!("Aborting the model loading with response: {msg}"),
+                Err(err) => error!("Got an error while invoking abort_model_load: {err}"),
+            };
+        });
+    };
+
     let on_click_add_model_config = move |ev: leptos::ev::SubmitEvent| {
         log!("on_click_load_model_config: {ev:#?}");
         ev.prevent_default();
@@ -109,7 +135,7 @@ pub fn Setting(cx: Scope) -> impl IntoView {
             let model_config_payload = PayloadModelConfig {
                 model_config: model_config(),
             };
-            match tauri::invoke::<PayloadModelConfig, String>(
+            match invoke::<PayloadModelConfig, String>(
                 "add_model_config",
                 &model_config_payload,
             )
@@ -129,15 +155,7 @@ pub fn Setting(cx: Scope) -> impl IntoView {
                 }
                 Err(err) => {
                     set_is_model_connected.update(|is_model_connected| *is_model_connected = true);
-                    match dialog::MessageDialogBuilder::new()
-                        .set_title("Add new model config")
-                        .set_kind(dialog::MessageDialogKind::Error)
-                        .message(format!("{err:#?}").as_str())
-                        .await
-                    {
-                        Ok(()) => (),
-                        Err(err) => error!("Dialog error add new model config: {err}"),
-                    };
+                    show_error("Add new model config", &err).await;
                 }
             };
         });
@@ -150,12 +168,19 @@ pub fn Setting(cx: Scope) -> impl IntoView {
             ev.prevent_default();
             spawn_local(async move {
                 let current_model_config = PayloadModelConfig {
-                    model_config: selected_model_config,
+                    model_config: selected_model_config.clone(),
                 };
-                // match tauri::invoke::<_, String>("load_model_config_simulated", &current_model_config).await {
-                match tauri::invoke::<_, String>("load_model_config", &current_model_config).await {
+                match invoke::<_, String>("load_model_config", &current_model_config).await {
                     Ok(msg) => {
                         set_model_config_loaded(ModelConfigState(true));
+                        // Apply the parameters saved with the model config
+                        if let Some(load_params) = selected_model_config.load_params.clone() {
+                            set_model_params(load_params);
+                        }
+                        if let Some(sampling) = selected_model_config.sampling.clone() {
+                            set_sampling_params(sampling);
+                        }
+                        set_loaded_model_config(Some(selected_model_config));
                         log!("Loading the model config with response: {msg}")
                     }
                     Err(err) => {
@@ -174,7 +199,7 @@ pub fn Setting(cx: Scope) -> impl IntoView {
                 let payload_id = PayloadId {
                     name: selected_model_config_name,
                 };
-                match tauri::invoke::<PayloadId, String>("delete_model_config", &payload_id)
+                match invoke::<PayloadId, String>("delete_model_config", &payload_id)
                     .await
                 {
                     Ok(msg) => {
@@ -187,21 +212,88 @@ pub fn Setting(cx: Scope) -> impl IntoView {
             });
         };
 
+    // Model config being edited in the table, with its original name
+    let (editing, set_editing) = create_signal(cx, None::<(String, ModelConfig)>);
+
+    let on_click_save_model_config = move |ev: leptos::ev::MouseEvent| {
+        ev.prevent_default();
+        let Some((name, edited)) = editing() else {
+            return;
+        };
+        let Some(original) = model_configs.with(|model_configs| {
+            model_configs
+                .iter()
+                .find(|model_config| model_config.name == name)
+                .cloned()
+        }) else {
+            return set_editing(None);
+        };
+        spawn_local(async move {
+            let mut name = name;
+            if edited.name != name {
+                let payload = PayloadRename {
+                    name: name.clone(),
+                    new_name: edited.name.clone(),
+                };
+                match invoke::<_, ModelConfig>("rename_model_config", &payload).await {
+                    Ok(renamed) => {
+                        set_loaded_model_config.update(|loaded_model_config| {
+                            if let Some(loaded_model_config) = loaded_model_config {
+                                if loaded_model_config.name == name {
+                                    *loaded_model_config = renamed.clone();
+                                }
+                            }
+                        });
+                        name = renamed.name;
+                    }
+                    Err(err) => return show_error("Rename model config", &err).await,
+                };
+            }
+            let patch = ModelConfigPatch::diff(&original, &edited);
+            if !patch.is_empty() {
+                match invoke::<_, ModelConfig>("update_model_config", &PayloadModelConfigPatch { name, patch }).await {
+                    Ok(updated) => log!("Model config updated: {updated:#?}"),
+                    Err(err) => return show_error("Update model config", &err).await,
+                };
+            }
+            set_editing(None);
+        });
+    };
+
+    let on_change_model_config_template = move |name: String, template_name: String| {
+        spawn_local(async move {
+            let payload = PayloadModelConfigTemplate {
+                name,
+                template_name: Some(template_name).filter(|name| !name.is_empty()),
+            };
+            match invoke::<_, ModelConfig>("set_model_config_template", &payload).await {
+                Ok(model_config) => log!("Model config template set: {model_config:#?}"),
+                Err(err) => show_error("Model config template", &err).await,
+            };
+        });
+    };
+
     let on_click_open_file = move |ev: leptos::ev::MouseEvent| {
         log!("on_click_open_file: {ev:#?}");
         ev.prevent_default();
         spawn_local(async move {
             match dialog::FileDialogBuilder::new()
                 .set_title("Pick a model")
-                .add_filter("Binary (.bin)", &["bin"])
+                .add_filter("Model (.bin, .gguf)", &["bin", "gguf"])
                 .pick_file()
                 .await
             {
                 Ok(Some(file_path)) => {
+                    set_model_metadata(None);
                     set_model_file_path
-                        .set(file_path.into_os_string().into_string().unwrap_or_default());
+                        .set(file_path.clone().into_os_string().into_string().unwrap_or_default());
                     // log!("Getting the model file path {:#?}", model_config())
-                    log!("Getting the model file path {:#?}", model_file_path())
+                    log!("Getting the model file path {:#?}", model_file_path());
+                    let metadata = invoke::<_, ModelMetadata>("inspect_model_file", &PayloadPath { path: file_path }).await;
+                    if let Ok(metadata) = &metadata {
+                        set_model_config.update(|model_config| model_config.model_architecture = metadata.architecture);
+                    }
+                    set_model_metadata(Some(metadata));
                 }
                 Ok(None) => {
                     warn!("Model file path picking canceled")
@@ -241,15 +333,56 @@ pub fn Setting(cx: Scope) -> impl IntoView {
             <input
                 type="checkbox"
                 class="toggle toggle-success"
-                prop:disabled=move || !model_config_loaded().0
+                prop:disabled=move || !model_config_loaded().0 || is_model_loading()
                 on:change=on_click_load_unload_model
-                prop:checked=is_model_connected
+                prop:checked=move || is_model_connected() || is_model_loading()
             />
         </div>
+        // Model loading progress
+        <Show when=is_model_loading fallback=|_| ()>
+            <div class="flex-0 flex flex-row items-center gap-4 border border-gray-700 rounded-lg m-2 p-2">
+                <progress
+                    class="progress progress-success flex-1"
+                    prop:value=move || match load_progress() {
+                        Some(LoadProgress::TensorLoaded { current_tensor, .. }) => current_tensor,
+                        Some(LoadProgress::Loaded { tensor_count, .. }) => tensor_count,
+                        _ => 0,
+                    }
+                    prop:max=move || match load_progress() {
+                        Some(LoadProgress::TensorLoaded { tensor_count, .. })
+                        | Some(LoadProgress::Loaded { tensor_count, .. }) => tensor_count,
+                        _ => 1,
+                    }
+                ></progress>
+                <span class="text-sm">
+                    {move || load_progress().map(|progress| progress.to_string()).unwrap_or("Loading the model".to_string())}
+                </span>
+                <button class="btn btn-sm" on:click=on_click_abort_model_load>
+                    <Icon class="h-4 w-4" icon=icon!(BsStopFill)/>
+                    "Abort"
+                </button>
+            </div>
+        </Show>
         // Advance settings
         <div class="flex-0 flex flex-row justify-between border border-gray-700 rounded-lg m-2 p-2">
             <ModelParamsDiv disabled=is_model_connected/>
         </div>
+        // Generation settings
+        <div class="flex-0 flex flex-row justify-between border border-gray-700 rounded-lg m-2 p-2">
+            <GenerationParamsDiv/>
+        </div>
+        // Prompt templates
+        <div class="flex-0 flex flex-row justify-between border border-gray-700 rounded-lg m-2 p-2">
+            <PromptTemplatesDiv/>
+        </div>
+        // Model library
+        <div class="flex-0 flex flex-row justify-between border border-gray-700 rounded-lg m-2 p-2">
+            <ModelLibraryDiv/>
+        </div>
+        // Local API server
+        <div class="flex-0 flex flex-row justify-between border border-gray-700 rounded-lg m-2 p-2">
+            <ApiServerDiv/>
+        </div>
         // Model Path
         <div
             class="flex flex-col flex-0 border border-gray-700 rounded-lg m-2 p-2"
@@ -299,26 +432,50 @@ pub fn Setting(cx: Scope) -> impl IntoView {
                             })
                     }
                 >
-                    <option disabled=true selected=true>
+                    <option disabled=true selected=move || model_metadata().is_none()>
                         "Choose the model type"
                     </option>
-                    <option>"Bloom"</option>
-                    <option>"Gpt2"</option>
-                    <option>"GptJ"</option>
-                    <option>"GptNeoX"</option>
-                    <option>"Llama"</option>
-                    <option>"Mpt"</option>
-                // <option>"Falcon"</option>
+                    {move || {
+                        architectures()
+                            .into_iter()
+                            .map(|architecture| {
+                                view! { cx,
+                                    <option selected=move || {
+                                        model_metadata().is_some()
+                                            && model_config().model_architecture == architecture
+                                    }>
+                                        {format!("{architecture:?}")}
+                                    </option>
+                                }
+                            })
+                            .collect_view(cx)
+                    }}
                 </select>
                 // Upload btn
                 <button
                     type="submit"
                     class="btn"
-                    prop:disabled=is_model_connected
+                    prop:disabled=move || is_model_connected() || is_model_file_invalid()
                 >
                     <Icon class="h-5 w-5" icon=icon!(BsDatabaseAdd)/>
                 </button>
             </form>
+            {move || match model_metadata() {
+                Some(Ok(metadata)) => view! { cx, <p class="text-xs mx-2 mt-2 opacity-70">{metadata.to_string()}</p> },
+                Some(Err(err)) => view! { cx,
+                    <p class="text-xs text-red-500 mx-2 mt-2">
+                        {format!("{err} {}", err.recovery().unwrap_or_default())}
+                    </p>
+                },
+                None => view! { cx, <p class="hidden"></p> },
+            }}
+            <TokenizerPicker
+                tokenizer_source=Signal::derive(cx, move || model_config().tokenizer_source)
+                set_tokenizer_source=SignalSetter::map(cx, move |tokenizer_source| {
+                    set_model_config.update(|model_config| model_config.tokenizer_source = tokenizer_source)
+                })
+                disabled=is_model_connected
+            />
         </div>
         // Availible models
         <div
@@ -334,30 +491,140 @@ pub fn Setting(cx: Scope) -> impl IntoView {
                             <th>"Path"</th>
                             <th>"Architecture"</th>
                             <th>"Tokinizer"</th>
+                            <th>"Template"</th>
                         </tr>
                     </thead>
                     <tbody>
                         <For
                             each=model_configs
-                            key=|model| model.name.clone()
+                            // Any change of the model config renders its row again
+                            key=|model| format!("{model:?}")
                             view=move |cx, model: ModelConfig| {
                                 let model_clone = model.clone();
+                                let model_edit = model.clone();
+                                let missing = model.missing;
+                                let is_editing = create_memo(cx, {
+                                    let name = model.name.clone();
+                                    move |_| editing.with(|editing| matches!(editing, Some((original, _)) if *original == name))
+                                });
+                                // Change a field of the edited model config
+                                let edit = move |change: &dyn Fn(&mut ModelConfig)| {
+                                    set_editing.update(|editing| {
+                                        if let Some((_, edited)) = editing {
+                                            change(edited);
+                                        }
+                                    })
+                                };
+                                let edited = move || editing().map(|(_, edited)| edited).unwrap_or_default();
+                                let name = model.name.clone();
+                                let path = model.model_path.display().to_string();
+                                let architecture = format!("{:?}", model.model_architecture);
                                 view! { cx,
-                                    <tr>
-                                        <th>{model.name.clone()}</th>
+                                    <tr class:opacity-50=missing>
+                                        <th>
+                                            <Show
+                                                when=is_editing
+                                                fallback=move |cx| view! { cx,
+                                                    {name.clone()}
+                                                    <Show when=move || missing fallback=|_| ()>
+                                                        <span class="badge badge-error badge-sm mx-2" title="The file was moved or deleted">"Missing"</span>
+                                                    </Show>
+                                                }
+                                            >
+                                                <input
+                                                    class="input input-sm"
+                                                    prop:value=move || edited().name
+                                                    on:change=move |ev| edit(&|edited| edited.name = event_target_value(&ev))
+                                                />
+                                            </Show>
+                                        </th>
+                                        <th>
+                                            <Show when=is_editing fallback=move |_| path.clone()>
+                                                <input
+                                                    class="input input-sm w-full"
+                                                    prop:value=move || edited().model_path.display().to_string()
+                                                    on:change=move |ev| edit(&|edited| edited.model_path = PathBuf::from(event_target_value(&ev)))
+                                                />
+                                            </Show>
+                                        </th>
                                         <th>
-                                            {format!(
-                                                "{}", model.model_path.clone().into_os_string()
-                                                .into_string().unwrap_or_default()
-                                            )}
+                                            <Show when=is_editing fallback=move |_| architecture.clone()>
+                                                <select
+                                                    class="select select-sm"
+                                                    on:change=move |ev| {
+                                                        edit(&|edited| {
+                                                            edited.model_architecture = ModelArchitecture::from_str(&event_target_value(&ev))
+                                                                .unwrap_or_default()
+                                                        })
+                                                    }
+                                                >
+                                                    {move || {
+                                                        architectures()
+                                                            .into_iter()
+                                                            .map(|architecture| {
+                                                                view! { cx,
+                                                                    <option selected=move || edited().model_architecture == architecture>
+                                                                        {format!("{architecture:?}")}
+                                                                    </option>
+                                                                }
+                                                            })
+                                                            .collect_view(cx)
+                                                    }}
+                                                </select>
+                                            </Show>
                                         </th>
-                                        <th>{format!("{:?}", model.model_architecture)}</th>
                                         <th>{format!("{:?}", model.tokenizer_source)}</th>
                                         <th>
+                                            <select
+                                                class="select select-sm"
+                                                on:change={
+                                                    let name = model.name.clone();
+                                                    move |ev| on_change_model_config_template(name.clone(), event_target_value(&ev))
+                                                }
+                                            >
+                                                <option value="" selected=model.template_name.is_none()>"None"</option>
+                                                {
+                                                    let template_name = model.template_name.clone();
+                                                    move || {
+                                                        prompt_templates()
+                                                            .into_iter()
+                                                            .map(|template| {
+                                                                let selected = template_name.as_ref() == Some(&template.name);
+                                                                view! { cx,
+                                                                    <option value=template.name.clone() selected=selected>
+                                                                        {template.name.clone()}
+                                                                    </option>
+                                                                }
+                                                            })
+                                                            .collect_view(cx)
+                                                    }
+                                                }
+                                            </select>
+                                        </th>
+                                        <th>
+                                            <Show
+                                                when=is_editing
+                                                fallback=move |cx| view! { cx,
+                                                    <button
+                                                        class="btn"
+                                                        title="Edit"
+                                                        disabled=move || editing().is_some()
+                                                        on:click={
+                                                            let model_edit = model_edit.clone();
+                                                            move |_| set_editing(Some((model_edit.name.clone(), model_edit.clone())))
+                                                        }
+                                                    >
+                                                        <Icon class="h-5 w-5" icon=icon!(AiEditOutlined)/>
+                                                    </button>
+                                                }
+                                            >
+                                                <button class="btn btn-success" on:click=on_click_save_model_config>"Save"</button>
+                                                <button class="btn" on:click=move |_| set_editing(None)>"Cancel"</button>
+                                            </Show>
                                             <button
                                                 class="btn"
                                                 // prop:disabled=is_model_connected
-                                                disabled=is_model_connected
+                                                disabled=move || is_model_connected() || missing
                                                 on:click=move |ev| {
                                                     on_click_load_model_config(ev, model.clone())
                                                 }
@@ -388,20 +655,190 @@ pub fn Setting(cx: Scope) -> impl IntoView {
 }
 
 
+/// Tokenizer of a new model config: the one embedded in the model, a tokenizer.json file or its
+/// pasted content. The backend checks it when the model config is added.
+#[component]
+fn TokenizerPicker(
+    cx: Scope,
+    tokenizer_source: Signal<TokenizerSource>,
+    set_tokenizer_source: SignalSetter<TokenizerSource>,
+    disabled: ReadSignal<bool>,
+) -> impl IntoView {
+    let kind = move || match tokenizer_source() {
+        TokenizerSource::Embedded => "embedded",
+        TokenizerSource::HuggingFaceTokenizerFile(_) => "file",
+        TokenizerSource::HuggingFaceTokenizerString(_) => "json",
+        TokenizerSource::HuggingFaceRemote(_) => "remote",
+    };
+    let on_change_kind = move |ev| {
+        set_tokenizer_source(match event_target_value(&ev).as_str() {
+            "file" => TokenizerSource::HuggingFaceTokenizerFile(PathBuf::new()),
+            "json" => TokenizerSource::HuggingFaceTokenizerString(String::new()),
+            "remote" => TokenizerSource::HuggingFaceRemote(String::new()),
+            _ => TokenizerSource::Embedded,
+        })
+    };
+    let on_click_pick_file = move |ev: leptos::ev::MouseEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            match dialog::FileDialogBuilder::new()
+                .set_title("Pick a tokenizer")
+                .add_filter("Tokenizer (.json)", &["json"])
+                .pick_file()
+                .await
+            {
+                Ok(Some(path)) => set_tokenizer_source(TokenizerSource::HuggingFaceTokenizerFile(path)),
+                Ok(None) => warn!("Tokenizer file picking canceled"),
+                Err(err) => error!("Got an error while picking a tokenizer: {err}"),
+            };
+        });
+    };
+
+    view! { cx,
+        <div class="flex flex-row gap-4 mt-2">
+            <select class="select select-sm" prop:disabled=disabled on:change=on_change_kind>
+                {[
+                    ("embedded", "Embedded tokenizer"),
+                    ("file", "tokenizer.json file"),
+                    ("json", "Pasted tokenizer.json"),
+                    ("remote", "Hugging Face repository"),
+                ]
+                    .into_iter()
+                    .map(|(value, label)| {
+                        view! { cx, <option value=value selected=move || kind() == value>{label}</option> }
+                    })
+                    .collect_view(cx)}
+            </select>
+            {move || match tokenizer_source() {
+                TokenizerSource::Embedded => view! { cx,
+                    <span class="text-xs self-center opacity-70">"The tokenizer stored in the model file is used"</span>
+                }
+                    .into_view(cx),
+                TokenizerSource::HuggingFaceTokenizerFile(path) => view! { cx,
+                    <div class="flex flex-1 gap-2">
+                        <button class="btn btn-sm" prop:disabled=disabled on:click=on_click_pick_file>
+                            "Pick"
+                        </button>
+                        <span class="text-sm self-center">{path.display().to_string()}</span>
+                    </div>
+                }
+                    .into_view(cx),
+                TokenizerSource::HuggingFaceTokenizerString(json) => view! { cx,
+                    <textarea
+                        class="textarea textarea-bordered flex-1 font-mono text-xs"
+                        placeholder="Paste the content of tokenizer.json"
+                        prop:disabled=disabled
+                        prop:value=json
+                        on:change=move |ev| set_tokenizer_source(
+                            TokenizerSource::HuggingFaceTokenizerString(event_target_value(&ev)),
+                        )
+                    ></textarea>
+                }
+                    .into_view(cx),
+                TokenizerSource::HuggingFaceRemote(identifier) => view! { cx,
+                    <input
+                        type="text"
+                        class="input input-sm flex-1"
+                        placeholder="owner/name"
+                        prop:disabled=disabled
+                        prop:value=identifier
+                        on:change=move |ev| set_tokenizer_source(
+                            TokenizerSource::HuggingFaceRemote(event_target_value(&ev)),
+                        )
+                    />
+                }
+                    .into_view(cx),
+            }}
+        </div>
+    }
+}
+
+/// Store parameters with the selected model config, they are applied each time it is selected
+fn save_default_button(
+    cx: Scope,
+    patch: impl Fn() -> ModelConfigPatch + Copy + 'static,
+) -> impl IntoView {
+    let (loaded_model_config, _) =
+        use_context::<(ReadSignal<Option<ModelConfig>>, WriteSignal<Option<ModelConfig>>)>(cx)
+            .expect("to have found the setter and getter provided for the loaded model config");
+    let on_click = move |_| {
+        let Some(model_config) = loaded_model_config() else {
+            return;
+        };
+        let payload = PayloadModelConfigPatch {
+            name: model_config.name,
+            patch: patch(),
+        };
+        spawn_local(async move {
+            match invoke::<_, ModelConfig>("update_model_config", &payload).await {
+                Ok(model_config) => log!("Model config defaults saved: {model_config:#?}"),
+                Err(err) => show_error("Model config defaults", &err).await,
+            };
+        });
+    };
+    view! { cx,
+        <button
+            class="btn"
+            prop:disabled=move || loaded_model_config().is_none()
+            title=move || {
+                loaded_model_config()
+                    .map(|model_config| format!("Use these values each time {} is selected", model_config.name))
+                    .unwrap_or_else(|| "Select a model config first".to_string())
+            }
+            on:click=on_click
+        >
+            "Save for model"
+        </button>
+    }
+}
+
 #[component]
 fn ModelParamsDiv(cx: Scope, disabled: ReadSignal<bool>) -> impl IntoView{
     let (model_params, set_model_params) =
     use_context::<(ReadSignal<ModelParameters>, WriteSignal<ModelParameters>)>(cx)
         .expect("to have found the setter and getter provided for model status");
 
+    let (loaded_model_config, _) =
+        use_context::<(ReadSignal<Option<ModelConfig>>, WriteSignal<Option<ModelConfig>>)>(cx)
+            .expect("to have found the setter and getter provided for the loaded model config");
+
     create_effect(cx, move |_| {
         log!("{:#?}", model_params());
     });
 
+    // The context the model was trained with, read from its header
+    let trained_context = create_local_resource(
+        cx,
+        move || loaded_model_config().map(|model_config| model_config.model_path),
+        |path| async move {
+            let path = path?;
+            match invoke::<_, ModelMetadata>("inspect_model_file", &PayloadPath { path }).await {
+                Ok(metadata) => metadata.context_size,
+                Err(err) => {
+                    warn!("Got an error while invoking inspect_model_file: {err}");
+                    None
+                }
+            }
+        },
+    );
+    let rope_warnings = move || {
+        let trained_context = trained_context
+            .read(cx)
+            .flatten()
+            .unwrap_or(DEFAULT_TRAINED_CONTEXT);
+        model_params().rope_warnings(trained_context)
+    };
+    let rope_enabled = move || model_params().rope_overrides.is_some();
+    let rope_overrides = move || model_params().rope_overrides.unwrap_or_default();
+
     view! { cx,
         <div class="flex flex-col justify-between p-2 w-full">
             <div class="flex w-full justify-between">
                 <h2>"Advanced"</h2>
+                {save_default_button(cx, move || ModelConfigPatch {
+                    load_params: Some(model_params()),
+                    ..Default::default()
+                })}
                 <button
                     class="btn"
                     prop:disabled=disabled
@@ -453,7 +890,7 @@ fn ModelParamsDiv(cx: Scope, disabled: ReadSignal<bool>) -> impl IntoView{
                     id="context_size"
                     type="range"
                     min=512
-                    max=4096
+                    max=16384
                     step=1
                     prop:disabled=disabled
                     prop:value=move || model_params().context_size
@@ -470,6 +907,798 @@ fn ModelParamsDiv(cx: Scope, disabled: ReadSignal<bool>) -> impl IntoView{
                 />
                 <span>{move || model_params().context_size}</span>
             </div>
+            <div class="flex w-full justify-between p-2">
+                <label for="rope_scaling">"RoPE scaling"</label>
+                <input
+                    id="rope_scaling"
+                    type="checkbox"
+                    prop:disabled=disabled
+                    class="toggle toggle-success"
+                    on:change=move |ev| {
+                        set_model_params
+                            .update(|model_params| {
+                                model_params.rope_overrides = event_target_checked(&ev)
+                                    .then(RoPEOverrides::default);
+                            })
+                    }
+                    prop:checked=rope_enabled
+                />
+            </div>
+            <Show when=rope_enabled fallback=|_| ()>
+                <div class="flex w-full justify-between p-2">
+                    <label class="whitespace-nowrap" for="rope_frequency_scale">
+                        "Frequency scale"
+                    </label>
+                    <input
+                        class="input input-bordered input-sm w-32"
+                        id="rope_frequency_scale"
+                        type="number"
+                        min=0.05
+                        step=0.05
+                        prop:disabled=disabled
+                        prop:value=move || rope_overrides().frequency_scale
+                        on:change=move |ev| {
+                            let Ok(frequency_scale) = event_target_value(&ev).parse::<f32>() else {
+                                return;
+                            };
+                            set_model_params
+                                .update(|model_params| {
+                                    if let Some(rope_overrides) = model_params.rope_overrides.as_mut() {
+                                        rope_overrides.frequency_scale = frequency_scale;
+                                    }
+                                })
+                        }
+                    />
+                </div>
+                <div class="flex w-full justify-between p-2">
+                    <label class="whitespace-nowrap" for="rope_frequency_base">
+                        "Frequency base"
+                    </label>
+                    <input
+                        class="input input-bordered input-sm w-32"
+                        id="rope_frequency_base"
+                        type="number"
+                        min=1
+                        step=1000
+                        prop:disabled=disabled
+                        prop:value=move || rope_overrides().frequency_base
+                        on:change=move |ev| {
+                            let Ok(frequency_base) = event_target_value(&ev).parse::<usize>() else {
+                                return;
+                            };
+                            set_model_params
+                                .update(|model_params| {
+                                    if let Some(rope_overrides) = model_params.rope_overrides.as_mut() {
+                                        rope_overrides.frequency_base = frequency_base;
+                                    }
+                                })
+                        }
+                    />
+                </div>
+            </Show>
+            <For
+                each=rope_warnings
+                key=|warning| warning.clone()
+                view=move |cx, warning: String| {
+                    view! { cx,
+                        <div class="alert alert-warning text-sm p-2">
+                            <span>{warning}</span>
+                        </div>
+                    }
+                }
+            />
+            <LoraAdaptersDiv disabled=disabled/>
+        </div>
+    }
+}
+
+/// File name of an adapter, the full path is in its title
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// LoRA adapters applied on top of the model, in order
+#[component]
+fn LoraAdaptersDiv(cx: Scope, disabled: ReadSignal<bool>) -> impl IntoView {
+    let (model_params, set_model_params) =
+        use_context::<(ReadSignal<ModelParameters>, WriteSignal<ModelParameters>)>(cx)
+            .expect("to have found the setter and getter provided for model status");
+    let (loaded_model_config, _) =
+        use_context::<(ReadSignal<Option<ModelConfig>>, WriteSignal<Option<ModelConfig>>)>(cx)
+            .expect("to have found the setter and getter provided for the loaded model config");
+
+    let adapters = move || model_params().lora_adapters.unwrap_or_default();
+    // No adapters is `None`, llm skips the LoRA patching then
+    let set_adapters = move |adapters: Vec<PathBuf>| {
+        set_model_params.update(|model_params| {
+            model_params.lora_adapters = (!adapters.is_empty()).then_some(adapters);
+        })
+    };
+    let recent_sets = move || {
+        loaded_model_config()
+            .map(|model_config| model_config.lora_adapter_sets)
+            .unwrap_or_default()
+    };
+    let move_adapter = move |index: usize, target: usize| {
+        let mut list = adapters();
+        if target < list.len() {
+            list.swap(index, target);
+            set_adapters(list);
+        }
+    };
+    let remove_adapter = move |index: usize| {
+        let mut list = adapters();
+        list.remove(index);
+        set_adapters(list);
+    };
+    let on_click_add = move |_| {
+        spawn_local(async move {
+            match dialog::FileDialogBuilder::new()
+                .set_title("Pick a LoRA adapter")
+                .add_filter("LoRA adapter (.bin)", &["bin"])
+                .pick_file()
+                .await
+            {
+                Ok(Some(path)) => {
+                    // Only the adapters the backend can read are added
+                    match invoke::<_, LoraMetadata>("inspect_lora_adapter", &PayloadPath { path: path.clone() }).await {
+                        Ok(metadata) => {
+                            log!("LoRA adapter {}: {metadata}", path.display());
+                            let mut list = adapters();
+                            if !list.contains(&path) {
+                                list.push(path);
+                                set_adapters(list);
+                            }
+                        }
+                        Err(err) => show_error("LoRA adapter", &err).await,
+                    }
+                }
+                Ok(None) => warn!("LoRA adapter picking canceled"),
+                Err(err) => error!("Got an error while picking a LoRA adapter: {err}"),
+            };
+        });
+    };
+
+    view! { cx,
+        <div class="flex flex-col w-full p-2">
+            <div class="flex w-full justify-between items-center">
+                <label>"LoRA adapters"</label>
+                <div class="flex gap-2">
+                    <Show when=move || !recent_sets().is_empty() fallback=|_| ()>
+                        <select
+                            class="select select-sm max-w-xs"
+                            prop:disabled=disabled
+                            on:change=move |ev| {
+                                let set = event_target_value(&ev)
+                                    .parse::<usize>()
+                                    .ok()
+                                    .and_then(|index| recent_sets().get(index).cloned());
+                                if let Some(set) = set {
+                                    set_adapters(set);
+                                }
+                            }
+                        >
+                            <option disabled=true selected=true>"Recent sets"</option>
+                            {move || {
+                                recent_sets()
+                                    .into_iter()
+                                    .enumerate()
+                                    .map(|(index, set)| {
+                                        let label = set.iter().map(|path| file_name(path)).collect::<Vec<_>>().join(", ");
+                                        view! { cx, <option value=index>{label}</option> }
+                                    })
+                                    .collect_view(cx)
+                            }}
+                        </select>
+                    </Show>
+                    <button class="btn btn-sm" prop:disabled=disabled on:click=on_click_add>
+                        "Add"
+                    </button>
+                </div>
+            </div>
+            <Show when=move || adapters().is_empty() fallback=|_| ()>
+                <p class="text-xs">"No adapter, the model is used as is"</p>
+            </Show>
+            <ul>
+                <For
+                    each=move || adapters().into_iter().enumerate().collect::<Vec<_>>()
+                    key=|(index, path)| (*index, path.clone())
+                    view=move |cx, (index, path): (usize, PathBuf)| {
+                        view! { cx,
+                            <li class="flex w-full justify-between items-center">
+                                <span class="text-sm" title=path.display().to_string()>
+                                    {format!("{}. {}", index + 1, file_name(&path))}
+                                </span>
+                                <div class="flex">
+                                    <button
+                                        class="btn btn-sm btn-ghost"
+                                        prop:disabled=move || disabled() || index == 0
+                                        on:click=move |_| move_adapter(index, index.saturating_sub(1))
+                                    >
+                                        <Icon class="h-4 w-4" icon=icon!(AiArrowUpOutlined)/>
+                                    </button>
+                                    <button
+                                        class="btn btn-sm btn-ghost"
+                                        prop:disabled=move || disabled() || index + 1 == adapters().len()
+                                        on:click=move |_| move_adapter(index, index + 1)
+                                    >
+                                        <Icon class="h-4 w-4" icon=icon!(AiArrowDownOutlined)/>
+                                    </button>
+                                    <button
+                                        class="btn btn-sm btn-ghost"
+                                        prop:disabled=disabled
+                                        on:click=move |_| remove_adapter(index)
+                                    >
+                                        <Icon class="h-4 w-4" icon=icon!(AiDeleteOutlined)/>
+                                    </button>
+                                </div>
+                            </li>
+                        }
+                    }
+                />
+            </ul>
+        </div>
+    }
+}
+
+#[component]
+fn GenerationParamsDiv(cx: Scope) -> impl IntoView {
+    let (sampling_params, set_sampling_params) =
+        use_context::<(ReadSignal<SamplingParameters>, WriteSignal<SamplingParameters>)>(cx)
+            .expect("to have found the setter and getter provided for sampling parameters");
+
+    create_effect(cx, move |_| {
+        log!("{:#?}", sampling_params());
+    });
+
+    view! { cx,
+        <div class="flex flex-col justify-between p-2 w-full">
+            <div class="flex w-full justify-between">
+                <h2>"Generation"</h2>
+                {save_default_button(cx, move || ModelConfigPatch {
+                    sampling: Some(sampling_params()),
+                    ..Default::default()
+                })}
+                <button
+                    class="btn"
+                    on:click=move |_| set_sampling_params(
+                        SamplingParameters::default(),
+                    )
+                >
+                    "Reset"
+                </button>
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="temperature">
+                    "Temperature"
+                </label>
+                <input
+                    class="range mx-4"
+                    id="temperature"
+                    type="range"
+                    min=0
+                    max=2
+                    step=0.05
+                    prop:value=move || sampling_params().sampler.temperature
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params
+                                    .sampler
+                                    .temperature = event_target_value(&ev)
+                                    .parse::<f32>()
+                                    .unwrap_or_default();
+                            })
+                    }
+                />
+                <span>{move || format!("{:.2}", sampling_params().sampler.temperature)}</span>
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="top_k">
+                    "Top-k"
+                </label>
+                <input
+                    class="range mx-4"
+                    id="top_k"
+                    type="range"
+                    min=1
+                    max=100
+                    step=1
+                    prop:value=move || sampling_params().sampler.top_k
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params
+                                    .sampler
+                                    .top_k = event_target_value(&ev)
+                                    .parse::<usize>()
+                                    .unwrap_or_default();
+                            })
+                    }
+                />
+                <span>{move || sampling_params().sampler.top_k}</span>
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="top_p">
+                    "Top-p"
+                </label>
+                <input
+                    class="range mx-4"
+                    id="top_p"
+                    type="range"
+                    min=0
+                    max=1
+                    step=0.01
+                    prop:value=move || sampling_params().sampler.top_p
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params
+                                    .sampler
+                                    .top_p = event_target_value(&ev)
+                                    .parse::<f32>()
+                                    .unwrap_or_default();
+                            })
+                    }
+                />
+                <span>{move || format!("{:.2}", sampling_params().sampler.top_p)}</span>
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="repeat_penalty">
+                    "Repeat penalty"
+                </label>
+                <input
+                    class="range mx-4"
+                    id="repeat_penalty"
+                    type="range"
+                    min=1
+                    max=2
+                    step=0.05
+                    prop:value=move || sampling_params().sampler.repeat_penalty
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params
+                                    .sampler
+                                    .repeat_penalty = event_target_value(&ev)
+                                    .parse::<f32>()
+                                    .unwrap_or_default();
+                            })
+                    }
+                />
+                <span>{move || format!("{:.2}", sampling_params().sampler.repeat_penalty)}</span>
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="repetition_penalty_last_n">
+                    "Repetition window"
+                </label>
+                <input
+                    class="range mx-4"
+                    id="repetition_penalty_last_n"
+                    type="range"
+                    min=0
+                    max=2048
+                    step=1
+                    prop:value=move || sampling_params().sampler.repetition_penalty_last_n
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params
+                                    .sampler
+                                    .repetition_penalty_last_n = event_target_value(&ev)
+                                    .parse::<usize>()
+                                    .unwrap_or_default();
+                            })
+                    }
+                />
+                <span>{move || sampling_params().sampler.repetition_penalty_last_n}</span>
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="maximum_token_count">
+                    "Maximum tokens"
+                </label>
+                <input
+                    class="input input-sm mx-4"
+                    id="maximum_token_count"
+                    type="number"
+                    min=1
+                    placeholder="Unlimited"
+                    prop:value=move || {
+                        sampling_params()
+                            .maximum_token_count
+                            .map(|maximum_token_count| maximum_token_count.to_string())
+                            .unwrap_or_default()
+                    }
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params
+                                    .maximum_token_count = event_target_value(&ev)
+                                    .parse::<usize>()
+                                    .ok();
+                            })
+                    }
+                />
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="seed">
+                    "Seed"
+                </label>
+                <input
+                    class="input input-sm mx-4"
+                    id="seed"
+                    type="number"
+                    min=0
+                    placeholder="Random"
+                    prop:value=move || {
+                        sampling_params()
+                            .seed
+                            .map(|seed| seed.to_string())
+                            .unwrap_or_default()
+                    }
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params
+                                    .seed = event_target_value(&ev)
+                                    .parse::<u64>()
+                                    .ok();
+                            })
+                    }
+                />
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="stop">
+                    "Stop strings"
+                </label>
+                <textarea
+                    class="textarea textarea-bordered textarea-sm mx-4"
+                    id="stop"
+                    rows="2"
+                    placeholder="One per line, added to the ones of the template"
+                    prop:value=move || sampling_params().stop.join("\n")
+                    on:change=move |ev| {
+                        set_sampling_params
+                            .update(|sampling_params| {
+                                sampling_params.stop = event_target_value(&ev)
+                                    .lines()
+                                    .filter(|stop| !stop.is_empty())
+                                    .map(str::to_owned)
+                                    .collect();
+                            })
+                    }
+                ></textarea>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn ModelLibraryDiv(cx: Scope) -> impl IntoView {
+    let (directories, set_directories) = create_signal(cx, Vec::<ModelDirectory>::new());
+    let (last_scan, set_last_scan) = create_signal(cx, None::<ScanReport>);
+
+    let refresh_directories = move || {
+        spawn_local(async move {
+            match invoke::<_, Vec<ModelDirectory>>("list_model_directories", &()).await {
+                Ok(list) => set_directories(list),
+                Err(err) => error!("Got an error while invoking list_model_directories: {err}"),
+            };
+        });
+    };
+    refresh_directories();
+
+    let on_click_add_directory = move |ev: leptos::ev::MouseEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            let path = match dialog::FileDialogBuilder::new()
+                .set_title("Pick a model directory")
+                .pick_folder()
+                .await
+            {
+                Ok(Some(path)) => path,
+                Ok(None) => return warn!("Model directory picking canceled"),
+                Err(err) => return error!("Got an error while picking a model directory: {err}"),
+            };
+            match invoke::<_, ScanReport>("add_model_directory", &PayloadPath { path }).await {
+                Ok(report) => set_last_scan(Some(report)),
+                Err(err) => show_error("Model library", &err).await,
+            };
+            refresh_directories();
+        });
+    };
+
+    let on_click_remove_directory = move |path: PathBuf| {
+        spawn_local(async move {
+            match invoke::<_, String>("delete_model_directory", &PayloadPath { path }).await {
+                Ok(msg) => log!("{msg}"),
+                Err(err) => show_error("Model library", &err).await,
+            };
+            refresh_directories();
+        });
+    };
+
+    let on_click_scan = move |ev: leptos::ev::MouseEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            match invoke::<_, ScanReport>("scan_model_library", &()).await {
+                Ok(report) => set_last_scan(Some(report)),
+                Err(err) => show_error("Model library", &err).await,
+            };
+        });
+    };
+
+    view! { cx,
+        <div class="flex flex-col justify-between p-2 w-full">
+            <div class="flex w-full justify-between">
+                <h2>"Model library"</h2>
+                <div class="flex gap-2">
+                    <button class="btn btn-sm" on:click=on_click_scan>"Scan"</button>
+                    <button class="btn btn-sm" on:click=on_click_add_directory>"Add a directory"</button>
+                </div>
+            </div>
+            <p class="text-xs mx-2">"The model files of these directories are added as model configs, new files are picked up while the app runs"</p>
+            <ul class="p-2">
+                <For
+                    each=directories
+                    key=|directory| directory.path.clone()
+                    view=move |cx, directory: ModelDirectory| {
+                        let path = directory.path.clone();
+                        view! { cx,
+                            <li class="flex w-full justify-between items-center">
+                                <span class="text-sm">{directory.path.display().to_string()}</span>
+                                <button
+                                    class="btn btn-sm btn-ghost"
+                                    on:click=move |_| on_click_remove_directory(path.clone())
+                                >
+                                    <Icon class="h-4 w-4" icon=icon!(AiDeleteOutlined)/>
+                                </button>
+                            </li>
+                        }
+                    }
+                />
+            </ul>
+            <Show when=move || last_scan().is_some() fallback=|_| ()>
+                <p class="text-xs mx-2">
+                    {move || last_scan().map(|report| format!("Last scan: {report}")).unwrap_or_default()}
+                </p>
+            </Show>
+        </div>
+    }
+}
+
+#[component]
+fn ApiServerDiv(cx: Scope) -> impl IntoView {
+    let (is_api_running, set_is_api_running) = create_signal(cx, false);
+    let (port, set_port) = create_signal(cx, 8080_u16);
+
+    // Sync with the backend, the server outlives this page
+    spawn_local(async move {
+        match invoke::<_, Option<u16>>("api_server_status", &()).await {
+            Ok(Some(running_port)) => {
+                set_port(running_port);
+                set_is_api_running(true);
+            }
+            Ok(None) => set_is_api_running(false),
+            Err(err) => error!("Got an error while invoking api_server_status: {err}"),
+        };
+    });
+
+    let on_change_api_server = move |ev| {
+        let start = event_target_checked(&ev);
+        spawn_local(async move {
+            let response = if start {
+                invoke::<_, String>("start_api_server", &PayloadPort { port: Some(port()) }).await
+            } else {
+                invoke::<_, String>("stop_api_server", &()).await
+            };
+            match response {
+                Ok(msg) => {
+                    set_is_api_running(start);
+                    log!("API server: {msg}");
+                }
+                Err(err) => {
+                    set_is_api_running(!start);
+                    show_error("API server", &err).await;
+                }
+            };
+        });
+    };
+
+    view! { cx,
+        <div class="flex flex-col justify-between p-2 w-full">
+            <div class="flex w-full justify-between">
+                <h2>"Local API server"</h2>
+                <input
+                    type="checkbox"
+                    class="toggle toggle-success"
+                    on:change=on_change_api_server
+                    prop:checked=is_api_running
+                />
+            </div>
+            <div class="flex w-full justify-between p-2">
+                <label class="whitespace-nowrap" for="api_port">
+                    "Port"
+                </label>
+                <input
+                    class="input input-sm mx-4"
+                    id="api_port"
+                    type="number"
+                    min=1024
+                    max=65535
+                    prop:disabled=is_api_running
+                    prop:value=port
+                    on:change=move |ev| {
+                        if let Ok(new_port) = event_target_value(&ev).parse::<u16>() {
+                            set_port(new_port);
+                        }
+                    }
+                />
+            </div>
+            <Show
+                when=is_api_running
+                fallback=|cx| view! { cx, <p class="text-xs mx-2">"Off, only reachable from this computer once started"</p> }
+            >
+                <p class="text-green-500 text-xs mx-2">
+                    {move || format!("OpenAI compatible API on http://127.0.0.1:{}/v1", port())}
+                </p>
+            </Show>
+        </div>
+    }
+}
+
+#[component]
+fn PromptTemplatesDiv(cx: Scope) -> impl IntoView {
+    let (prompt_templates, _) =
+        use_context::<(ReadSignal<Vec<PromptTemplate>>, WriteSignal<Vec<PromptTemplate>>)>(cx)
+            .expect("to have found the setter and getter provided for prompt templates");
+    // Template being written, an existing custom template is updated on submit
+    let (template, set_template) = create_signal(cx, PromptTemplate::default());
+    let is_update = move || {
+        let name = template.with(|template| template.name.clone());
+        prompt_templates.with(|templates| {
+            templates
+                .iter()
+                .any(|template| !template.builtin && template.name == name)
+        })
+    };
+
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        let command = if is_update() {
+            "update_prompt_template"
+        } else {
+            "add_prompt_template"
+        };
+        spawn_local(async move {
+            let payload = PayloadTemplate {
+                template: template(),
+            };
+            match invoke::<_, String>(command, &payload).await {
+                Ok(msg) => {
+                    set_template(PromptTemplate::default());
+                    log!("Prompt template saved with response: {msg}")
+                }
+                Err(err) => show_error("Prompt template", &err).await,
+            };
+        });
+    };
+
+    let on_click_delete = move |name: String| {
+        spawn_local(async move {
+            match invoke::<_, String>("delete_prompt_template", &PayloadId { name }).await {
+                Ok(msg) => log!("Prompt template deleted with response: {msg}"),
+                Err(err) => show_error("Prompt template", &err).await,
+            };
+        });
+    };
+
+    view! { cx,
+        <div class="flex flex-col justify-between p-2 w-full">
+            <h2 class="mb-3">"Prompt templates"</h2>
+            <table class="table table-sm">
+                <tbody>
+                    <For
+                        each=prompt_templates
+                        key=|template| template.name.clone()
+                        view=move |cx, prompt_template: PromptTemplate| {
+                            let builtin = prompt_template.builtin;
+                            let name = prompt_template.name.clone();
+                            view! { cx,
+                                <tr>
+                                    <th>{prompt_template.name.clone()}</th>
+                                    <th>{if builtin { "Built-in" } else { "Custom" }}</th>
+                                    <th>
+                                        <button
+                                            class="btn btn-sm"
+                                            on:click={
+                                                let prompt_template = prompt_template.clone();
+                                                move |_| set_template(PromptTemplate {
+                                                    builtin: false,
+                                                    ..prompt_template.clone()
+                                                })
+                                            }
+                                        >
+                                            <Icon class="h-4 w-4" icon=icon!(AiEditOutlined)/>
+                                        </button>
+                                        <button
+                                            class="btn btn-sm"
+                                            disabled=builtin
+                                            on:click=move |_| on_click_delete(name.clone())
+                                        >
+                                            <Icon class="h-4 w-4" icon=icon!(AiDeleteOutlined)/>
+                                        </button>
+                                    </th>
+                                </tr>
+                            }
+                        }
+                    />
+                </tbody>
+            </table>
+            <form class="flex flex-col gap-2 mt-2" on:submit=on_submit>
+                <input
+                    class="input input-sm input-bordered"
+                    type="text"
+                    placeholder="Name"
+                    required=true
+                    prop:value=move || template().name
+                    on:change=move |ev| set_template.update(|template| template.name = event_target_value(&ev))
+                />
+                <input
+                    class="input input-sm input-bordered"
+                    type="text"
+                    placeholder="Layout, with {{SYSTEM}}, {{HISTORY}} and {{PROMPT}}"
+                    required=true
+                    prop:value=move || template().layout
+                    on:change=move |ev| set_template.update(|template| template.layout = event_target_value(&ev))
+                />
+                <textarea
+                    class="textarea textarea-bordered textarea-sm"
+                    placeholder="System"
+                    rows="2"
+                    prop:value=move || template().system
+                    on:change=move |ev| set_template.update(|template| template.system = event_target_value(&ev))
+                ></textarea>
+                <textarea
+                    class="textarea textarea-bordered textarea-sm"
+                    placeholder="User, {{PROMPT}} is replaced by the message"
+                    rows="2"
+                    prop:value=move || template().user
+                    on:change=move |ev| set_template.update(|template| template.user = event_target_value(&ev))
+                ></textarea>
+                <textarea
+                    class="textarea textarea-bordered textarea-sm"
+                    placeholder="Assistant, {{PROMPT}} is replaced by the answer"
+                    rows="2"
+                    prop:value=move || template().assistant
+                    on:change=move |ev| set_template.update(|template| template.assistant = event_target_value(&ev))
+                ></textarea>
+                <textarea
+                    class="textarea textarea-bordered textarea-sm"
+                    placeholder="Stop strings, one per line"
+                    rows="2"
+                    prop:value=move || template().stop.join("\n")
+                    on:change=move |ev| {
+                        set_template
+                            .update(|template| {
+                                template.stop = event_target_value(&ev)
+                                    .lines()
+                                    .filter(|stop| !stop.is_empty())
+                                    .map(str::to_owned)
+                                    .collect();
+                            })
+                    }
+                ></textarea>
+                <button
+                    type="submit"
+                    class="btn btn-sm"
+                    prop:disabled=move || !template().is_valid()
+                >
+                    {move || if is_update() { "Update the template" } else { "Add the template" }}
+                </button>
+            </form>
         </div>
     }
 }
@@ -0,0 +1,508 @@
+use std::path::PathBuf;
+
+use chrono::{Local, TimeZone};
+use leptos::*;
+use leptos_icons::*;
+use tauri_sys::dialog;
+
+use crate::{
+    invoke, show_error, BenchmarkConfig, BenchmarkProgress, BenchmarkResult, ExportFormat,
+    InferenceRecord, InferenceStats, InferenceSummary, PayloadBenchmarkConfig, PayloadBenchmarkId,
+    PayloadExport, PayloadModelName,
+};
+
+/// Colors of the models, in the order the models first appear in the records
+const COLORS: &[&str] = &["#22c55e", "#3b82f6", "#f97316", "#a855f7", "#ef4444", "#14b8a6"];
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 200.0;
+const CHART_MARGIN: f64 = 40.0;
+
+fn color(index: usize) -> &'static str {
+    COLORS[index % COLORS.len()]
+}
+
+fn format_time(timestamp: i64) -> String {
+    Local
+        .timestamp_millis_opt(timestamp)
+        .single()
+        .map(|time| time.format("%e %b %Y, %R").to_string())
+        .unwrap_or_default()
+}
+
+/// How the model was run for its latest prediction
+fn setup(record: &InferenceRecord) -> String {
+    format!(
+        "{:?}, {} tokens context, {} threads{}",
+        record.model_architecture,
+        record.load_params.context_size,
+        record.threads,
+        if record.load_params.use_gpu { ", GPU" } else { "" }
+    )
+}
+
+fn benchmark_setup(result: &BenchmarkResult) -> String {
+    format!(
+        "{:?}, {} threads{}, {} + {} tokens x{}",
+        result.model_architecture,
+        result.threads,
+        if result.load_params.use_gpu { ", GPU" } else { "" },
+        result.config.prompt_tokens,
+        result.config.generation_tokens,
+        result.config.repetitions
+    )
+}
+
+/// Values of a model over time
+#[derive(Clone, Debug, PartialEq)]
+struct Series {
+    model_name: String,
+    color: &'static str,
+    /// (timestamp, value)
+    points: Vec<(i64, f64)>,
+}
+
+fn series(
+    records: &[InferenceRecord],
+    metric: fn(&InferenceStats) -> f64,
+    color_of: impl Fn(&str) -> &'static str,
+) -> Vec<Series> {
+    let mut series: Vec<Series> = Vec::new();
+    for record in records {
+        let index = match series
+            .iter()
+            .position(|series| series.model_name == record.model_name)
+        {
+            Some(index) => index,
+            None => {
+                series.push(Series {
+                    model_name: record.model_name.clone(),
+                    color: color_of(&record.model_name),
+                    points: Vec::new(),
+                });
+                series.len() - 1
+            }
+        };
+        series[index]
+            .points
+            .push((record.timestamp, metric(&record.stats)));
+    }
+    series
+}
+
+#[component]
+pub fn Stats(cx: Scope) -> impl IntoView {
+    let (records, set_records) = create_signal(cx, Vec::<InferenceRecord>::new());
+    // `None` shows every model
+    let (model_name, set_model_name) = create_signal(cx, None::<String>);
+
+    let refresh = move || {
+        spawn_local(async move {
+            match invoke::<_, Vec<InferenceRecord>>("list_inference_records", &PayloadModelName { model_name: None }).await {
+                Ok(list) => set_records(list),
+                Err(err) => show_error("Inference stats", &err).await,
+            };
+        });
+    };
+    refresh();
+
+    let on_click_clear = move |_| {
+        spawn_local(async move {
+            match invoke::<_, String>("clear_inference_records", &PayloadModelName { model_name: model_name() }).await {
+                Ok(msg) => log!("{msg}"),
+                Err(err) => show_error("Inference stats", &err).await,
+            };
+            set_model_name(None);
+            refresh();
+        });
+    };
+
+    let model_names = move || {
+        InferenceSummary::per_model(&records())
+            .into_iter()
+            .map(|summary| summary.model_name)
+            .collect::<Vec<_>>()
+    };
+    let shown_records = move || {
+        let records = records();
+        match model_name() {
+            Some(model_name) => records
+                .into_iter()
+                .filter(|record| record.model_name == model_name)
+                .collect(),
+            None => records,
+        }
+    };
+    // The colors follow the models in all the records, not only the shown ones
+    let color_of = move |name: &str| {
+        model_names()
+            .iter()
+            .position(|model_name| model_name == name)
+            .map(color)
+            .unwrap_or(COLORS[0])
+    };
+    let chart = move |metric: fn(&InferenceStats) -> f64| {
+        Signal::derive(cx, move || series(&shown_records(), metric, color_of))
+    };
+
+    view! { cx,
+        <div class="flex flex-col gap-4 p-4">
+            <div class="flex w-full justify-between items-center gap-2">
+                <h2 class="text-xl font-bold">"Inference stats"</h2>
+                <select
+                    class="select select-bordered select-sm"
+                    on:change=move |ev| {
+                        set_model_name(Some(event_target_value(&ev)).filter(|name| !name.is_empty()))
+                    }
+                >
+                    <option value="" selected=move || model_name().is_none()>"All models"</option>
+                    {move || {
+                        model_names()
+                            .into_iter()
+                            .map(|name| {
+                                let selected = model_name() == Some(name.clone());
+                                view! { cx, <option value=name.clone() selected=selected>{name.clone()}</option> }
+                            })
+                            .collect_view(cx)
+                    }}
+                </select>
+                <div class="flex gap-2">
+                    <button class="btn btn-sm" on:click=move |_| refresh()>"Refresh"</button>
+                    <button class="btn btn-sm btn-error" on:click=on_click_clear>
+                        {move || match model_name() {
+                            Some(_) => "Clear model",
+                            None => "Clear all",
+                        }}
+                    </button>
+                </div>
+            </div>
+            <Show
+                when=move || !shown_records().is_empty()
+                fallback=|cx| view! { cx, <p class="text-center">"No prediction was recorded yet"</p> }
+            >
+                <table class="table table-sm">
+                    <thead>
+                        <tr>
+                            <th>"Model"</th>
+                            <th>"Setup"</th>
+                            <th>"Predictions"</th>
+                            <th>"Tokens/s"</th>
+                            <th>"Prompt feed (ms)"</th>
+                            <th>"Per token (ms)"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || {
+                            let records = shown_records();
+                            InferenceSummary::per_model(&records)
+                                .into_iter()
+                                .map(|summary| {
+                                    let model_setup = records
+                                        .iter()
+                                        .rev()
+                                        .find(|record| record.model_name == summary.model_name)
+                                        .map(setup)
+                                        .unwrap_or_default();
+                                    let color = color_of(&summary.model_name);
+                                    view! { cx,
+                                        <tr>
+                                            <td>
+                                                <span style=format!("color: {color}")>"● "</span>
+                                                {summary.model_name}
+                                            </td>
+                                            <td>{model_setup}</td>
+                                            <td>{summary.predictions}</td>
+                                            <td>{format!("{:.2}", summary.tokens_per_second)}</td>
+                                            <td>{format!("{:.1}", summary.feed_prompt_millis)}</td>
+                                            <td>{format!("{:.1}", summary.per_token_millis)}</td>
+                                        </tr>
+                                    }
+                                })
+                                .collect_view(cx)
+                        }}
+                    </tbody>
+                </table>
+                <LineChart title="Tokens per second" unit="tokens/s" series=chart(InferenceStats::tokens_per_second)/>
+                <LineChart title="Prompt feed time" unit="ms" series=chart(InferenceStats::feed_prompt_millis)/>
+                <LineChart title="Per-token latency" unit="ms" series=chart(InferenceStats::per_token_millis)/>
+            </Show>
+            <Benchmarks model_name=model_name/>
+        </div>
+    }
+}
+
+/// Run the benchmark suite against the loaded model and compare the results
+#[component]
+fn Benchmarks(cx: Scope, model_name: ReadSignal<Option<String>>) -> impl IntoView {
+    let (is_model_connected, _) = use_context::<(ReadSignal<bool>, WriteSignal<bool>)>(cx)
+        .expect("to have found the getter provided for model status");
+    let (benchmark_progress, set_benchmark_progress) =
+        use_context::<(ReadSignal<Option<BenchmarkProgress>>, WriteSignal<Option<BenchmarkProgress>>)>(cx)
+            .expect("to have found the setter and getter provided for the benchmark progress");
+    let (config, set_config) = create_signal(cx, BenchmarkConfig::default());
+    let (benchmarks, set_benchmarks) = create_signal(cx, Vec::<BenchmarkResult>::new());
+    let (is_running, set_is_running) = create_signal(cx, false);
+
+    let refresh = move || {
+        spawn_local(async move {
+            match invoke::<_, Vec<BenchmarkResult>>("list_benchmarks", &PayloadModelName { model_name: None }).await {
+                Ok(list) => set_benchmarks(list),
+                Err(err) => show_error("Benchmarks", &err).await,
+            };
+        });
+    };
+    refresh();
+
+    let on_click_run = move |_| {
+        set_is_running(true);
+        set_benchmark_progress(None);
+        spawn_local(async move {
+            match invoke::<_, BenchmarkResult>("run_benchmark", &PayloadBenchmarkConfig { config: config() }).await {
+                Ok(result) => log!("Benchmark {} done", result.benchmark_id),
+                Err(err) => show_error("Benchmark", &err).await,
+            };
+            set_is_running(false);
+            refresh();
+        });
+    };
+    let on_click_stop = move |_| {
+        spawn_local(async move {
+            match invoke::<_, String>("stop_prediction", &()).await {
+                Ok(msg) => log!("{msg}"),
+                Err(err) => error!("Got an error while invoking stop_prediction: {err}"),
+            };
+        });
+    };
+    let export = move |format: ExportFormat| {
+        let (filter_name, extension) = match format {
+            ExportFormat::Csv => ("CSV", "csv"),
+            ExportFormat::Json => ("JSON", "json"),
+        };
+        spawn_local(async move {
+            let default_path = PathBuf::from(format!("benchmarks.{extension}"));
+            match dialog::FileDialogBuilder::new()
+                .set_title("Export the benchmarks")
+                .set_default_path(&default_path)
+                .add_filter(filter_name, &[extension])
+                .save_file()
+                .await
+            {
+                Ok(Some(path)) => {
+                    match invoke::<_, String>("export_benchmarks", &PayloadExport { path, format, model_name: model_name() }).await {
+                        Ok(msg) => log!("{msg}"),
+                        Err(err) => show_error("Benchmark export", &err).await,
+                    };
+                }
+                Ok(None) => warn!("Benchmark export canceled"),
+                Err(err) => error!("Export file picking failed: {err}"),
+            }
+        });
+    };
+    let delete = move |benchmark_id: String| {
+        spawn_local(async move {
+            match invoke::<_, String>("delete_benchmark", &PayloadBenchmarkId { benchmark_id }).await {
+                Ok(msg) => log!("{msg}"),
+                Err(err) => show_error("Benchmark", &err).await,
+            };
+            refresh();
+        });
+    };
+    let shown_benchmarks = move || {
+        let benchmarks = benchmarks();
+        match model_name() {
+            Some(model_name) => benchmarks
+                .into_iter()
+                .filter(|result| result.model_name == model_name)
+                .collect(),
+            None => benchmarks,
+        }
+    };
+    let size_input = move |label: &'static str, value: fn(&BenchmarkConfig) -> usize, set: fn(&mut BenchmarkConfig, usize)| {
+        view! { cx,
+            <label class="flex flex-col text-xs">
+                {label}
+                <input
+                    class="input input-bordered input-sm w-32"
+                    type="number"
+                    min=1
+                    prop:disabled=is_running
+                    prop:value=move || value(&config())
+                    on:change=move |ev| {
+                        if let Ok(size) = event_target_value(&ev).parse::<usize>() {
+                            set_config.update(|config| set(config, size));
+                        }
+                    }
+                />
+            </label>
+        }
+    };
+
+    view! { cx,
+        <div class="flex flex-col gap-2">
+            <div class="flex w-full justify-between items-center gap-2">
+                <h2 class="text-xl font-bold">"Benchmarks"</h2>
+                <div class="flex gap-2">
+                    <button class="btn btn-sm" on:click=move |_| export(ExportFormat::Csv)>"Export CSV"</button>
+                    <button class="btn btn-sm" on:click=move |_| export(ExportFormat::Json)>"Export JSON"</button>
+                </div>
+            </div>
+            <div class="flex w-full items-end gap-2">
+                {size_input("Prompt tokens", |config| config.prompt_tokens, |config, size| config.prompt_tokens = size)}
+                {size_input("Generated tokens", |config| config.generation_tokens, |config, size| config.generation_tokens = size)}
+                {size_input("Repetitions", |config| config.repetitions, |config, size| config.repetitions = size)}
+                <Show
+                    when=is_running
+                    fallback=move |cx| view! { cx,
+                        <button
+                            class="btn btn-sm btn-success"
+                            prop:disabled=move || !is_model_connected()
+                            on:click=on_click_run
+                        >
+                            "Run"
+                        </button>
+                    }
+                >
+                    <button class="btn btn-sm btn-error" on:click=on_click_stop>"Stop"</button>
+                </Show>
+            </div>
+            <Show when=is_running fallback=|_| ()>
+                <progress
+                    class="progress progress-success w-full"
+                    max=move || benchmark_progress().map(|progress| progress.total).unwrap_or(1)
+                    value=move || benchmark_progress().map(|progress| progress.done).unwrap_or_default()
+                ></progress>
+            </Show>
+            <table class="table table-sm">
+                <thead>
+                    <tr>
+                        <th>"Date"</th>
+                        <th>"Model"</th>
+                        <th>"Setup"</th>
+                        <th>"Prompt eval (tokens/s)"</th>
+                        <th>"Generation (tokens/s)"</th>
+                        <th>"Peak memory"</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=shown_benchmarks
+                        key=|result| result.benchmark_id.clone()
+                        view=move |cx, result: BenchmarkResult| {
+                            let benchmark_id = result.benchmark_id.clone();
+                            view! { cx,
+                                <tr>
+                                    <td>{format_time(result.timestamp)}</td>
+                                    <td>{result.model_name.clone()}</td>
+                                    <td>{benchmark_setup(&result)}</td>
+                                    <td>{format!("{:.2} ± {:.2}", result.prompt_eval.mean, result.prompt_eval.std_dev)}</td>
+                                    <td>{format!("{:.2} ± {:.2}", result.generation.mean, result.generation.std_dev)}</td>
+                                    <td>
+                                        {result
+                                            .peak_memory_bytes
+                                            .map(|bytes| format!("{} MB", bytes / (1024 * 1024)))
+                                            .unwrap_or_else(|| "unsupported".to_string())}
+                                    </td>
+                                    <td>
+                                        <button
+                                            class="btn btn-ghost btn-xs"
+                                            title="Delete the benchmark"
+                                            on:click=move |_| delete(benchmark_id.clone())
+                                        >
+                                            <Icon icon=icon!(AiDeleteOutlined)/>
+                                        </button>
+                                    </td>
+                                </tr>
+                            }
+                        }
+                    />
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+/// Values of the models over time, from zero to the highest value
+#[component]
+fn LineChart(
+    cx: Scope,
+    title: &'static str,
+    unit: &'static str,
+    series: Signal<Vec<Series>>,
+) -> impl IntoView {
+    // (first timestamp, last timestamp, highest value)
+    let bounds = move || {
+        series.with(|series| {
+            let mut bounds = (i64::MAX, i64::MIN, 0.0_f64);
+            for &(timestamp, value) in series.iter().flat_map(|series| &series.points) {
+                bounds.0 = bounds.0.min(timestamp);
+                bounds.1 = bounds.1.max(timestamp);
+                bounds.2 = bounds.2.max(value);
+            }
+            match bounds {
+                (start, end, _) if start > end => (0, 1, 1.0),
+                (start, end, top) => (start, end.max(start + 1), if top > 0.0 { top } else { 1.0 }),
+            }
+        })
+    };
+    let position = move |timestamp: i64, value: f64| {
+        let (start, end, top) = bounds();
+        let x = CHART_MARGIN
+            + (timestamp - start) as f64 / (end - start) as f64 * (CHART_WIDTH - 2.0 * CHART_MARGIN);
+        let y = CHART_HEIGHT - CHART_MARGIN - value / top * (CHART_HEIGHT - 2.0 * CHART_MARGIN);
+        (x, y)
+    };
+    let bottom = CHART_HEIGHT - CHART_MARGIN;
+    let right = CHART_WIDTH - CHART_MARGIN;
+    let baseline = CHART_HEIGHT - 8.0;
+
+    view! { cx,
+        <div class="flex flex-col p-2 rounded-box bg-base-200">
+            <h3 class="font-bold">{title}</h3>
+            <svg class="w-full" viewBox=format!("0 0 {CHART_WIDTH} {CHART_HEIGHT}")>
+                <line x1=CHART_MARGIN y1=CHART_MARGIN x2=CHART_MARGIN y2=bottom stroke="currentColor" stroke-opacity="0.4"/>
+                <line x1=CHART_MARGIN y1=bottom x2=right y2=bottom stroke="currentColor" stroke-opacity="0.4"/>
+                <text x=4 y=CHART_MARGIN font-size="10" fill="currentColor">
+                    {move || format!("{:.1} {unit}", bounds().2)}
+                </text>
+                <text x=4 y=bottom font-size="10" fill="currentColor">"0"</text>
+                <text x=CHART_MARGIN y=baseline font-size="10" fill="currentColor">
+                    {move || format_time(bounds().0)}
+                </text>
+                <text x=right y=baseline font-size="10" fill="currentColor" text-anchor="end">
+                    {move || format_time(bounds().1)}
+                </text>
+                {move || {
+                    series()
+                        .into_iter()
+                        .map(|series| {
+                            let points = series
+                                .points
+                                .iter()
+                                .map(|&(timestamp, value)| {
+                                    let (x, y) = position(timestamp, value);
+                                    format!("{x:.1},{y:.1}")
+                                })
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            let marks = series
+                                .points
+                                .iter()
+                                .map(|&(timestamp, value)| {
+                                    let (x, y) = position(timestamp, value);
+                                    let (x, y) = (x - 2.0, y - 2.0);
+                                    view! { cx, <rect x=x y=y width=4 height=4 fill=series.color/> }
+                                })
+                                .collect_view(cx);
+                            view! { cx,
+                                <g>
+                                    <polyline points=points fill="none" stroke=series.color stroke-width="2"/>
+                                    {marks}
+                                </g>
+                            }
+                        })
+                        .collect_view(cx)
+                }}
+            </svg>
+        </div>
+    }
+}
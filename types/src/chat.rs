@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, InferenceStats};
+
+/// Placeholder replaced by the message in the user and assistant parts, and by the new turn in
+/// the layout
+pub const PROMPT_PLACEHOLDER: &str = "{{PROMPT}}";
+/// Placeholder of the layout replaced by the system part
+pub const SYSTEM_PLACEHOLDER: &str = "{{SYSTEM}}";
+/// Placeholder of the layout replaced by the previous turns
+pub const HISTORY_PLACEHOLDER: &str = "{{HISTORY}}";
+pub const DEFAULT_LAYOUT: &str = "{{SYSTEM}}{{HISTORY}}{{PROMPT}}";
+/// Template used when neither the conversation nor the model config picked one
+pub const DEFAULT_TEMPLATE: &str = "Default";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Entity {
+    #[default]
+    User,
+    Bot,
+}
+
+/// A previous message of the conversation, rendered by the backend with the template
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatTurn {
+    pub entity: Entity,
+    pub content: String,
+}
+
+impl ChatTurn {
+    pub fn new(entity: Entity, content: impl Into<String>) -> Self {
+        Self {
+            entity,
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Conversation {
+    pub conversation_id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// Prompt template picked for the conversation, the model config one is used otherwise
+    #[serde(default)]
+    pub template_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoredMessage {
+    pub conversation_id: String,
+    /// Position of the message in the conversation
+    pub position: usize,
+    pub content: String,
+    pub entity: Entity,
+    pub timestamp: i64,
+    pub model_name: Option<String>,
+    pub stats: Option<InferenceStats>,
+}
+
+/// Size of a conversation rendered for a new session, compared to the context
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenCount {
+    pub used: usize,
+    /// Tokens kept for the answer
+    pub reserved: usize,
+    pub context_size: usize,
+}
+
+impl TokenCount {
+    /// The oldest turns are dropped when the conversation overflows
+    pub fn overflows(&self) -> bool {
+        self.used + self.reserved > self.context_size
+    }
+
+    /// Tokens available for the conversation
+    pub fn available(&self) -> usize {
+        self.context_size.saturating_sub(self.reserved)
+    }
+}
+
+/// A conversation is rendered with the `layout`, each turn formatted with the `user` or
+/// `assistant` part whose `{{PROMPT}}` is replaced by the message. The prediction starts after
+/// the text of the `assistant` part preceding `{{PROMPT}}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    /// Order of the `{{SYSTEM}}`, `{{HISTORY}}` and `{{PROMPT}}` placeholders
+    #[serde(default = "default_layout")]
+    pub layout: String,
+    pub system: String,
+    pub user: String,
+    pub assistant: String,
+    /// The prediction is cut at the first of these strings
+    pub stop: Vec<String>,
+    /// Presets are not stored in the database and can not be changed
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+fn default_layout() -> String {
+    DEFAULT_LAYOUT.to_string()
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            layout: default_layout(),
+            system: String::new(),
+            user: String::new(),
+            assistant: String::new(),
+            stop: Vec::new(),
+            builtin: false,
+        }
+    }
+}
+
+impl PromptTemplate {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::invalid_input("template name", "the name is empty"));
+        }
+        if !self.layout.contains(PROMPT_PLACEHOLDER) {
+            return Err(AppError::invalid_input(
+                "layout",
+                format!("it should contain {PROMPT_PLACEHOLDER}"),
+            ));
+        }
+        if !self.user.contains(PROMPT_PLACEHOLDER) {
+            return Err(AppError::invalid_input(
+                "user part",
+                format!("it should contain {PROMPT_PLACEHOLDER}"),
+            ));
+        }
+        if !self.assistant.contains(PROMPT_PLACEHOLDER) {
+            return Err(AppError::invalid_input(
+                "assistant part",
+                format!("it should contain {PROMPT_PLACEHOLDER}"),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// The layout with the system part and the user part of the new turn
+    pub fn preview(&self) -> String {
+        let (assistant_prefix, _) = self
+            .assistant
+            .split_once(PROMPT_PLACEHOLDER)
+            .unwrap_or((self.assistant.as_str(), ""));
+        self.layout
+            .replace(SYSTEM_PLACEHOLDER, &self.system)
+            .replace(PROMPT_PLACEHOLDER, &format!("{}{assistant_prefix}", self.user))
+    }
+}
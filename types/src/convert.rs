@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use crate::{
+    AppError, InferenceStats, ModelArchitecture, ModelParameters, RoPEOverrides, SamplingParameters,
+    TokenizerSource, TopPTopK,
+};
+
+impl ModelArchitecture {
+    /// The llm architecture, `None` when llm was built without it
+    pub fn to_llm(self) -> Option<llm::ModelArchitecture> {
+        Some(match self {
+            ModelArchitecture::Bloom => llm::ModelArchitecture::Bloom,
+            ModelArchitecture::Gpt2 => llm::ModelArchitecture::Gpt2,
+            ModelArchitecture::GptJ => llm::ModelArchitecture::GptJ,
+            ModelArchitecture::GptNeoX => llm::ModelArchitecture::GptNeoX,
+            ModelArchitecture::Llama => llm::ModelArchitecture::Llama,
+            ModelArchitecture::Mpt => llm::ModelArchitecture::Mpt,
+            #[cfg(feature = "falcon")]
+            ModelArchitecture::Falcon => llm::ModelArchitecture::Falcon,
+            #[cfg(not(feature = "falcon"))]
+            ModelArchitecture::Falcon => return None,
+        })
+    }
+
+    /// The architectures this build can load
+    pub fn supported() -> Vec<ModelArchitecture> {
+        ModelArchitecture::ALL
+            .iter()
+            .copied()
+            .filter(|architecture| architecture.to_llm().is_some())
+            .collect()
+    }
+}
+
+impl From<TokenizerSource> for llm::TokenizerSource {
+    fn from(tokenizer_source: TokenizerSource) -> Self {
+        match tokenizer_source {
+            TokenizerSource::Embedded => llm::TokenizerSource::Embedded,
+            TokenizerSource::HuggingFaceTokenizerFile(path) => {
+                llm::TokenizerSource::HuggingFaceTokenizerFile(path)
+            }
+            TokenizerSource::HuggingFaceRemote(identifier) => {
+                llm::TokenizerSource::HuggingFaceRemote(identifier)
+            }
+            TokenizerSource::HuggingFaceTokenizerString(json) => {
+                llm::TokenizerSource::HuggingFaceTokenizerString(json)
+            }
+        }
+    }
+}
+
+// A new llm tokenizer source fails this match
+impl From<llm::TokenizerSource> for TokenizerSource {
+    fn from(tokenizer_source: llm::TokenizerSource) -> Self {
+        match tokenizer_source {
+            llm::TokenizerSource::Embedded => TokenizerSource::Embedded,
+            llm::TokenizerSource::HuggingFaceTokenizerFile(path) => {
+                TokenizerSource::HuggingFaceTokenizerFile(path)
+            }
+            llm::TokenizerSource::HuggingFaceRemote(identifier) => {
+                TokenizerSource::HuggingFaceRemote(identifier)
+            }
+            llm::TokenizerSource::HuggingFaceTokenizerString(json) => {
+                TokenizerSource::HuggingFaceTokenizerString(json)
+            }
+        }
+    }
+}
+
+impl From<RoPEOverrides> for llm::RoPEOverrides {
+    fn from(rope_overrides: RoPEOverrides) -> Self {
+        Self {
+            frequency_scale: rope_overrides.frequency_scale,
+            frequency_base: rope_overrides.frequency_base,
+        }
+    }
+}
+
+impl From<&llm::RoPEOverrides> for RoPEOverrides {
+    fn from(rope_overrides: &llm::RoPEOverrides) -> Self {
+        Self {
+            frequency_scale: rope_overrides.frequency_scale,
+            frequency_base: rope_overrides.frequency_base,
+        }
+    }
+}
+
+impl From<ModelParameters> for llm::ModelParameters {
+    fn from(model_params: ModelParameters) -> Self {
+        Self {
+            prefer_mmap: model_params.prefer_mmap,
+            context_size: model_params.context_size,
+            lora_adapters: model_params.lora_adapters,
+            use_gpu: model_params.use_gpu,
+            gpu_layers: model_params.gpu_layers,
+            rope_overrides: model_params.rope_overrides.map(Into::into),
+        }
+    }
+}
+
+impl From<&llm::ModelParameters> for ModelParameters {
+    fn from(model_params: &llm::ModelParameters) -> Self {
+        Self {
+            prefer_mmap: model_params.prefer_mmap,
+            context_size: model_params.context_size,
+            lora_adapters: model_params.lora_adapters.clone(),
+            use_gpu: model_params.use_gpu,
+            gpu_layers: model_params.gpu_layers,
+            rope_overrides: model_params.rope_overrides.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<TopPTopK> for llm::samplers::TopPTopK {
+    fn from(sampler: TopPTopK) -> Self {
+        Self {
+            top_k: sampler.top_k,
+            top_p: sampler.top_p,
+            repeat_penalty: sampler.repeat_penalty,
+            temperature: sampler.temperature,
+            repetition_penalty_last_n: sampler.repetition_penalty_last_n,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&llm::InferenceStats> for InferenceStats {
+    fn from(stats: &llm::InferenceStats) -> Self {
+        Self {
+            feed_prompt_duration: stats.feed_prompt_duration,
+            prompt_tokens: stats.prompt_tokens,
+            predict_duration: stats.predict_duration,
+            predict_tokens: stats.predict_tokens,
+        }
+    }
+}
+
+impl SamplingParameters {
+    pub fn inference_parameters(&self) -> llm::InferenceParameters {
+        let sampler: llm::samplers::TopPTopK = self.sampler.clone().into();
+        llm::InferenceParameters {
+            sampler: Arc::new(sampler),
+        }
+    }
+}
+
+impl From<llm::InferenceError> for AppError {
+    fn from(err: llm::InferenceError) -> Self {
+        AppError::InferenceFailed {
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl From<llm::TokenizationError> for AppError {
+    fn from(err: llm::TokenizationError) -> Self {
+        AppError::InferenceFailed {
+            reason: err.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_llm() {
+        assert_eq!(
+            ModelParameters::from(&llm::ModelParameters::default()),
+            ModelParameters::default()
+        );
+        let sampler = llm::samplers::TopPTopK::default();
+        let default = TopPTopK::default();
+        assert_eq!(
+            (sampler.top_k, sampler.top_p, sampler.repeat_penalty, sampler.temperature),
+            (default.top_k, default.top_p, default.repeat_penalty, default.temperature)
+        );
+        assert_eq!(sampler.repetition_penalty_last_n, default.repetition_penalty_last_n);
+        assert_eq!(
+            RoPEOverrides::from(&llm::RoPEOverrides::default()),
+            RoPEOverrides::default()
+        );
+    }
+
+    #[test]
+    fn falcon_depends_on_the_feature() {
+        let supported = ModelArchitecture::supported();
+        assert!(supported.contains(&ModelArchitecture::Llama));
+        assert_eq!(supported.contains(&ModelArchitecture::Falcon), cfg!(feature = "falcon"));
+    }
+
+    #[test]
+    fn tokenizer_sources_convert_both_ways() {
+        for tokenizer_source in [
+            TokenizerSource::Embedded,
+            TokenizerSource::HuggingFaceTokenizerFile("/models/tokenizer.json".into()),
+            TokenizerSource::HuggingFaceRemote("hf-internal-testing/llama-tokenizer".to_string()),
+            TokenizerSource::HuggingFaceTokenizerString("{}".to_string()),
+        ] {
+            let converted: llm::TokenizerSource = tokenizer_source.clone().into();
+            assert_eq!(TokenizerSource::from(converted), tokenizer_source);
+        }
+    }
+}
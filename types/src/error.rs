@@ -0,0 +1,192 @@
+use std::{path::PathBuf, sync::PoisonError};
+
+use serde::{Deserialize, Serialize};
+use strum::IntoStaticStr;
+
+/// Error returned by the commands, serialized as `{ "code": "...", ...fields }` so the frontend
+/// can match on the code instead of parsing the message
+#[derive(Debug, Clone, PartialEq, thiserror::Error, Serialize, Deserialize, IntoStaticStr)]
+#[serde(tag = "code", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AppError {
+    #[error("No model loaded")]
+    ModelNotLoaded,
+    #[error("No model config selected")]
+    NoModelConfig,
+    #[error("Model config {name} not found")]
+    ConfigNotFound { name: String },
+    #[error("{what} not found")]
+    NotFound { what: String },
+    #[error("{what} already exists")]
+    AlreadyExists { what: String },
+    #[error("Invalid {field}: {reason}")]
+    InvalidInput { field: String, reason: String },
+    #[error("Database not connected, please reconnect to the database")]
+    DbNotConnected,
+    #[error("Database error: {reason}")]
+    Db { reason: String },
+    #[error("Failed to load {}: {reason}", path.display())]
+    LoadFailed { path: PathBuf, reason: String },
+    #[error("{} is not a supported model: {reason}", path.display())]
+    UnsupportedModel { path: PathBuf, reason: String },
+    #[error("Model loading aborted")]
+    LoadAborted,
+    #[error("A model is already loading")]
+    LoadInProgress,
+    #[error("Benchmark stopped")]
+    BenchmarkStopped,
+    #[error("Inference failed: {reason}")]
+    InferenceFailed { reason: String },
+    #[error("API server error: {reason}")]
+    ApiServer { reason: String },
+    #[error("IO error: {reason}")]
+    Io { reason: String },
+    #[error("Internal error: {reason}")]
+    Internal { reason: String },
+}
+
+impl AppError {
+    /// Machine readable code, same as the serialized `code` field
+    pub fn code(&self) -> &'static str {
+        self.into()
+    }
+
+    pub fn not_found(what: impl Into<String>) -> Self {
+        AppError::NotFound { what: what.into() }
+    }
+
+    pub fn already_exists(what: impl Into<String>) -> Self {
+        AppError::AlreadyExists { what: what.into() }
+    }
+
+    pub fn invalid_input(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        AppError::InvalidInput {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// A failed query, the connection is kept
+    pub fn db(reason: impl ToString) -> Self {
+        AppError::Db {
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn io(reason: impl ToString) -> Self {
+        AppError::Io {
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn internal(reason: impl ToString) -> Self {
+        AppError::Internal {
+            reason: reason.to_string(),
+        }
+    }
+
+    /// What the user can do about the error
+    pub fn recovery(&self) -> Option<&'static str> {
+        match self {
+            AppError::ModelNotLoaded => Some("Load a model from the settings page."),
+            AppError::NoModelConfig => Some("Pick a model config in the settings page first."),
+            AppError::ConfigNotFound { .. } => Some("The model config was removed, add it again."),
+            AppError::AlreadyExists { .. } => Some("Pick another name."),
+            AppError::InvalidInput { .. } => Some("Fix the value and try again."),
+            AppError::DbNotConnected => Some("Reconnecting to the database, try again in a moment."),
+            AppError::LoadFailed { .. } => {
+                Some("Check the model file and its architecture, or lower the context size.")
+            }
+            AppError::UnsupportedModel { .. } => {
+                Some("Pick a GGML model, GGUF files need a GGML (GGJT) conversion for now.")
+            }
+            AppError::InferenceFailed { .. } => {
+                Some("Start a new chat, the context may be full.")
+            }
+            AppError::LoadInProgress => Some("Wait for the loading to end or abort it."),
+            AppError::ApiServer { .. } => Some("Try another port."),
+            AppError::NotFound { .. }
+            | AppError::Db { .. }
+            | AppError::LoadAborted
+            | AppError::BenchmarkStopped
+            | AppError::Io { .. }
+            | AppError::Internal { .. } => None,
+        }
+    }
+
+    /// The frontend reconnects when the database is gone, a failed query keeps the connection
+    pub fn needs_reconnect(&self) -> bool {
+        matches!(self, AppError::DbNotConnected)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::io(err)
+    }
+}
+
+/// A poisoned lock means a thread panicked while holding it
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(err: PoisonError<T>) -> Self {
+        AppError::internal(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_the_code_with_the_fields() {
+        let err = AppError::LoadFailed {
+            path: PathBuf::from("model.bin"),
+            reason: "bad magic".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({ "code": "load_failed", "path": "model.bin", "reason": "bad magic" })
+        );
+        assert_eq!(
+            serde_json::to_value(AppError::ModelNotLoaded).unwrap(),
+            serde_json::json!({ "code": "model_not_loaded" })
+        );
+    }
+
+    #[test]
+    fn code_matches_the_serialized_code_and_round_trips() {
+        for err in [
+            AppError::ModelNotLoaded,
+            AppError::NoModelConfig,
+            AppError::ConfigNotFound { name: String::new() },
+            AppError::not_found(""),
+            AppError::already_exists(""),
+            AppError::invalid_input("", ""),
+            AppError::DbNotConnected,
+            AppError::Db { reason: String::new() },
+            AppError::LoadFailed { path: PathBuf::new(), reason: String::new() },
+            AppError::UnsupportedModel { path: PathBuf::new(), reason: String::new() },
+            AppError::LoadAborted,
+            AppError::LoadInProgress,
+            AppError::BenchmarkStopped,
+            AppError::InferenceFailed { reason: String::new() },
+            AppError::ApiServer { reason: String::new() },
+            AppError::Io { reason: String::new() },
+            AppError::internal(""),
+        ] {
+            let value = serde_json::to_value(&err).unwrap();
+            assert_eq!(value["code"], err.code());
+            assert_eq!(serde_json::from_value::<AppError>(value).unwrap(), err);
+        }
+    }
+
+    #[test]
+    fn only_a_lost_connection_needs_a_reconnect() {
+        assert!(AppError::DbNotConnected.needs_reconnect());
+        let err = AppError::Db {
+            reason: "Found 'x' for field `name`".to_string(),
+        };
+        assert!(!err.needs_reconnect());
+        assert_eq!(err.recovery(), None);
+    }
+}
@@ -0,0 +1,688 @@
+//! Types exchanged between the frontend and the backend, both sides serialize them the same way.
+//! The `llm` feature adds the conversions to the llm types and the errors of the backend.
+
+use std::{path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use strum::EnumString;
+
+mod chat;
+#[cfg(feature = "llm")]
+mod convert;
+mod error;
+mod library;
+
+pub use chat::{
+    ChatTurn, Conversation, Entity, PromptTemplate, StoredMessage, TokenCount, DEFAULT_LAYOUT,
+    DEFAULT_TEMPLATE, HISTORY_PLACEHOLDER, PROMPT_PLACEHOLDER, SYSTEM_PLACEHOLDER,
+};
+pub use error::AppError;
+pub use library::{LoadProgress, LoraMetadata, ModelDirectory, ModelMetadata, ScanReport};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, EnumString, Default, PartialEq, Eq, Hash)]
+#[strum(ascii_case_insensitive)]
+pub enum ModelArchitecture {
+    Bloom,
+    Gpt2,
+    GptJ,
+    GptNeoX,
+    #[default]
+    Llama,
+    Mpt,
+    /// Only loaded by a backend built with the `falcon` feature
+    Falcon,
+}
+
+impl ModelArchitecture {
+    /// Every architecture of the llm revision, the backend lists the ones it was built with
+    pub const ALL: &'static [ModelArchitecture] = &[
+        ModelArchitecture::Bloom,
+        ModelArchitecture::Gpt2,
+        ModelArchitecture::GptJ,
+        ModelArchitecture::GptNeoX,
+        ModelArchitecture::Llama,
+        ModelArchitecture::Mpt,
+        ModelArchitecture::Falcon,
+    ];
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum TokenizerSource {
+    #[default]
+    Embedded,
+    HuggingFaceTokenizerFile(PathBuf),
+    HuggingFaceRemote(String),
+    /// Content of a tokenizer.json
+    HuggingFaceTokenizerString(String),
+}
+
+/// Context length assumed when the model file does not store the one it was trained with
+pub const DEFAULT_TRAINED_CONTEXT: usize = 2048;
+/// Frequency base of the original RoPE
+pub const ROPE_FREQUENCY_BASE: usize = 10_000;
+
+/// Scaling of the rotary position embeddings, extended context fine-tunes expect a linear scale
+/// below 1 or a larger frequency base
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RoPEOverrides {
+    pub frequency_scale: f32,
+    pub frequency_base: usize,
+}
+
+impl Default for RoPEOverrides {
+    fn default() -> Self {
+        Self {
+            frequency_scale: 1.0,
+            frequency_base: ROPE_FREQUENCY_BASE,
+        }
+    }
+}
+
+impl RoPEOverrides {
+    /// Why llm can not use these values
+    pub fn check(&self) -> Result<(), String> {
+        if !(self.frequency_scale.is_finite() && self.frequency_scale > 0.0) {
+            return Err(format!("the frequency scale {} should be above 0", self.frequency_scale));
+        }
+        if self.frequency_base == 0 {
+            return Err("the frequency base should be above 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Roughly how many times the trained context the model handles, a scale of 0.5 or a base
+    /// of 20 000 double it
+    pub fn context_factor(&self) -> f32 {
+        self.frequency_base as f32 / ROPE_FREQUENCY_BASE as f32 / self.frequency_scale
+    }
+}
+
+/// Parameters of the model loading
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelParameters {
+    pub prefer_mmap: bool,
+    pub context_size: usize,
+    pub lora_adapters: Option<Vec<PathBuf>>,
+    pub use_gpu: bool,
+    pub gpu_layers: Option<usize>,
+    #[serde(default)]
+    pub rope_overrides: Option<RoPEOverrides>,
+}
+
+impl Default for ModelParameters {
+    fn default() -> Self {
+        Self {
+            prefer_mmap: true,
+            context_size: 2048,
+            lora_adapters: None,
+            use_gpu: false,
+            gpu_layers: None,
+            rope_overrides: None,
+        }
+    }
+}
+
+impl ModelParameters {
+    /// Mismatches between the context size and the RoPE scaling of a model trained with
+    /// `trained_context` tokens, the model loads anyway
+    pub fn rope_warnings(&self, trained_context: usize) -> Vec<String> {
+        let rope_overrides = self.rope_overrides.unwrap_or_default();
+        if let Err(reason) = rope_overrides.check() {
+            return vec![format!("The RoPE scaling is refused, {reason}")];
+        }
+        let factor = rope_overrides.context_factor();
+        let handled = (trained_context as f32 * factor) as usize;
+        if self.context_size > handled {
+            vec![format!(
+                "The model handles about {handled} tokens with this scaling, the answers degrade past it"
+            )]
+        } else if factor > 1.0 && self.context_size <= trained_context {
+            vec![format!(
+                "The model was trained with {trained_context} tokens, the scaling is not needed and degrades the answers"
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// The top-p top-k sampler of llm, without the token biases
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopPTopK {
+    pub top_k: usize,
+    pub top_p: f32,
+    pub repeat_penalty: f32,
+    pub temperature: f32,
+    pub repetition_penalty_last_n: usize,
+}
+
+impl Default for TopPTopK {
+    fn default() -> Self {
+        Self {
+            top_k: 40,
+            top_p: 0.95,
+            repeat_penalty: 1.30,
+            temperature: 0.80,
+            repetition_penalty_last_n: 512,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SamplingParameters {
+    pub sampler: TopPTopK,
+    pub maximum_token_count: Option<usize>,
+    pub seed: Option<u64>,
+    /// The prediction halts before the first of these strings
+    #[serde(default)]
+    pub stop: Vec<String>,
+}
+
+/// Statistics of a prediction, stored with the answer
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct InferenceStats {
+    /// How long it took to feed the prompt.
+    pub feed_prompt_duration: Duration,
+    /// How many tokens the prompt was.
+    pub prompt_tokens: usize,
+    /// How long it took to predict new tokens.
+    pub predict_duration: Duration,
+    /// The number of predicted tokens.
+    pub predict_tokens: usize,
+}
+
+impl std::fmt::Display for InferenceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Self {
+            feed_prompt_duration,
+            prompt_tokens,
+            predict_duration,
+            predict_tokens,
+        } = *self;
+
+        let per_token_duration = self.per_token_millis();
+        let feed_prompt_duration = feed_prompt_duration.as_millis();
+        let predict_duration = predict_duration.as_millis();
+
+        writeln!(f, "feed_prompt_duration: {}ms", feed_prompt_duration)?;
+        writeln!(f, "prompt_tokens: {}", prompt_tokens)?;
+        writeln!(f, "predict_duration: {}ms", predict_duration)?;
+        writeln!(f, "predict_tokens: {}", predict_tokens)?;
+        write!(f, "per_token_duration: {:.3}ms", per_token_duration)
+    }
+}
+
+impl InferenceStats {
+    /// Predicted tokens per second, 0 without predicted tokens
+    pub fn tokens_per_second(&self) -> f64 {
+        match self.predict_duration.as_secs_f64() {
+            seconds if seconds > 0.0 => self.predict_tokens as f64 / seconds,
+            _ => 0.0,
+        }
+    }
+
+    /// Milliseconds spent on each predicted token
+    pub fn per_token_millis(&self) -> f64 {
+        match self.predict_tokens {
+            0 => 0.0,
+            predict_tokens => self.predict_duration.as_secs_f64() * 1000.0 / predict_tokens as f64,
+        }
+    }
+
+    pub fn feed_prompt_millis(&self) -> f64 {
+        self.feed_prompt_duration.as_secs_f64() * 1000.0
+    }
+
+    /// Prompt tokens fed per second, 0 without a prompt
+    pub fn prompt_tokens_per_second(&self) -> f64 {
+        match self.feed_prompt_duration.as_secs_f64() {
+            seconds if seconds > 0.0 => self.prompt_tokens as f64 / seconds,
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::ops::AddAssign for InferenceStats {
+    fn add_assign(&mut self, other: Self) {
+        self.feed_prompt_duration += other.feed_prompt_duration;
+        self.prompt_tokens += other.prompt_tokens;
+        self.predict_duration += other.predict_duration;
+        self.predict_tokens += other.predict_tokens;
+    }
+}
+
+/// Stats of a prediction with how the model was run, kept to compare the models
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct InferenceRecord {
+    pub model_name: String,
+    pub model_architecture: ModelArchitecture,
+    pub load_params: ModelParameters,
+    /// Threads of the inference session
+    pub threads: usize,
+    /// Milliseconds since the unix epoch
+    pub timestamp: i64,
+    pub stats: InferenceStats,
+}
+
+/// Averages of the predictions of a model
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct InferenceSummary {
+    pub model_name: String,
+    pub predictions: usize,
+    pub tokens_per_second: f64,
+    pub feed_prompt_millis: f64,
+    pub per_token_millis: f64,
+}
+
+impl InferenceSummary {
+    /// One summary per model, in the order the models first appear
+    pub fn per_model(records: &[InferenceRecord]) -> Vec<InferenceSummary> {
+        let mut summaries: Vec<InferenceSummary> = Vec::new();
+        for record in records {
+            let index = match summaries
+                .iter()
+                .position(|summary| summary.model_name == record.model_name)
+            {
+                Some(index) => index,
+                None => {
+                    summaries.push(InferenceSummary {
+                        model_name: record.model_name.clone(),
+                        ..Default::default()
+                    });
+                    summaries.len() - 1
+                }
+            };
+            let summary = &mut summaries[index];
+            summary.predictions += 1;
+            summary.tokens_per_second += record.stats.tokens_per_second();
+            summary.feed_prompt_millis += record.stats.feed_prompt_millis();
+            summary.per_token_millis += record.stats.per_token_millis();
+        }
+        for summary in &mut summaries {
+            let predictions = summary.predictions as f64;
+            summary.tokens_per_second /= predictions;
+            summary.feed_prompt_millis /= predictions;
+            summary.per_token_millis /= predictions;
+        }
+        summaries
+    }
+}
+
+/// Sizes of a benchmark, the suite of prompts is run once per repetition
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkConfig {
+    /// Tokens of each prompt
+    pub prompt_tokens: usize,
+    /// Tokens generated for each prompt, fewer when the model ends its answer
+    pub generation_tokens: usize,
+    pub repetitions: usize,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            prompt_tokens: 128,
+            generation_tokens: 64,
+            repetitions: 3,
+        }
+    }
+}
+
+impl BenchmarkConfig {
+    /// The prompt and the generation must fit in the context of the loaded model
+    pub fn check(&self, context_size: usize) -> Result<(), String> {
+        if self.prompt_tokens == 0 || self.generation_tokens == 0 || self.repetitions == 0 {
+            return Err("the sizes must be above 0".to_string());
+        }
+        match self.prompt_tokens + self.generation_tokens {
+            tokens if tokens > context_size => Err(format!(
+                "{tokens} tokens do not fit in the context of {context_size} tokens"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Stats of a prompt of the suite in a repetition
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct BenchmarkSample {
+    /// Index of the prompt in the suite
+    pub prompt: usize,
+    pub repetition: usize,
+    pub stats: InferenceStats,
+}
+
+/// Spread of a throughput over the repetitions, in tokens per second
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Throughput {
+    pub mean: f64,
+    /// Sample standard deviation, 0 with a single repetition
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Throughput {
+    pub fn of(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let std_dev = match values.len() {
+            1 => 0.0,
+            _ => (values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (count - 1.0)).sqrt(),
+        };
+        Self {
+            mean,
+            std_dev,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// A benchmark of the loaded model with how it was run
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BenchmarkResult {
+    pub benchmark_id: String,
+    pub model_name: String,
+    pub model_architecture: ModelArchitecture,
+    pub load_params: ModelParameters,
+    /// Threads of the inference session
+    pub threads: usize,
+    /// Milliseconds since the unix epoch
+    pub timestamp: i64,
+    pub config: BenchmarkConfig,
+    pub samples: Vec<BenchmarkSample>,
+    /// Prompt tokens fed per second
+    pub prompt_eval: Throughput,
+    /// Tokens generated per second
+    pub generation: Throughput,
+    /// Highest resident memory of the app sampled during the run, `None` on the systems it is
+    /// not measured on (only Linux is supported)
+    pub peak_memory_bytes: Option<u64>,
+}
+
+impl BenchmarkResult {
+    /// Throughputs of each repetition over the whole suite, as (prompt eval, generation)
+    pub fn throughputs(samples: &[BenchmarkSample], repetitions: usize) -> (Throughput, Throughput) {
+        let totals = (0..repetitions)
+            .map(|repetition| {
+                let mut total = InferenceStats::default();
+                for sample in samples.iter().filter(|sample| sample.repetition == repetition) {
+                    total += sample.stats;
+                }
+                total
+            })
+            .collect::<Vec<_>>();
+        let prompt_eval = totals.iter().map(InferenceStats::prompt_tokens_per_second).collect::<Vec<_>>();
+        let generation = totals.iter().map(InferenceStats::tokens_per_second).collect::<Vec<_>>();
+        (Throughput::of(&prompt_eval), Throughput::of(&generation))
+    }
+}
+
+/// Runs of a benchmark done so far, emitted as `benchmark_progress` events
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BenchmarkProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+/// File formats of the benchmark export
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ModelConfig {
+    pub name: String,
+    pub model_architecture: ModelArchitecture,
+    pub model_path: PathBuf,
+    pub tokenizer_source: TokenizerSource,
+    /// Prompt template used by the conversations that did not pick one
+    #[serde(default)]
+    pub template_name: Option<String>,
+    /// The file was moved or deleted since the last scan of the model library
+    #[serde(default)]
+    pub missing: bool,
+    /// Load parameters used when this model config is loaded
+    #[serde(default)]
+    pub load_params: Option<ModelParameters>,
+    /// Sampling parameters picked when this model config is loaded
+    #[serde(default)]
+    pub sampling: Option<SamplingParameters>,
+    /// LoRA adapter sets loaded with this model config, the most recent first
+    #[serde(default)]
+    pub lora_adapter_sets: Vec<Vec<PathBuf>>,
+}
+
+/// Arguments of `add_model_config` and `load_model_config`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PayloadModelConfig {
+    #[serde(rename(serialize = "modelConfig"))]
+    pub model_config: ModelConfig,
+}
+
+/// Arguments of `load_dynamic_model`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PayloadModelParams {
+    pub params: ModelParams,
+}
+
+/// The `params` argument of `load_dynamic_model`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ModelParams {
+    pub model_params: ModelParameters,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+
+    /// JSON of every tokenizer source, the model configs stored in the database use it
+    const TOKENIZER_SOURCES: &str = include_str!("../fixtures/tokenizer_sources.json");
+
+    /// Tauri hands each argument of a command to the backend by its name
+    fn argument<T: DeserializeOwned>(payload: &impl Serialize, name: &str) -> T {
+        let payload = serde_json::to_value(payload).unwrap();
+        serde_json::from_value(payload[name].clone()).unwrap()
+    }
+
+    fn model_config() -> ModelConfig {
+        ModelConfig {
+            name: "llama".to_string(),
+            model_architecture: ModelArchitecture::GptNeoX,
+            model_path: PathBuf::from("/models/llama.bin"),
+            tokenizer_source: TokenizerSource::HuggingFaceTokenizerFile(PathBuf::from("/models/tokenizer.json")),
+            template_name: Some("Alpaca".to_string()),
+            missing: false,
+            load_params: Some(ModelParameters {
+                lora_adapters: Some(vec![PathBuf::from("/models/lora.bin")]),
+                rope_overrides: Some(RoPEOverrides::default()),
+                ..Default::default()
+            }),
+            sampling: Some(SamplingParameters {
+                seed: Some(42),
+                stop: vec!["User:".to_string()],
+                ..Default::default()
+            }),
+            lora_adapter_sets: vec![vec![PathBuf::from("/models/lora.bin")]],
+        }
+    }
+
+    fn record(model_name: &str, predict_tokens: usize, predict_millis: u64) -> InferenceRecord {
+        InferenceRecord {
+            model_name: model_name.to_string(),
+            stats: InferenceStats {
+                feed_prompt_duration: Duration::from_millis(200),
+                prompt_tokens: 20,
+                predict_duration: Duration::from_millis(predict_millis),
+                predict_tokens,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn inference_stats_are_summarized_per_model() {
+        let empty = record("llama", 0, 0).stats;
+        assert_eq!((empty.tokens_per_second(), empty.per_token_millis()), (0.0, 0.0));
+        let summaries = InferenceSummary::per_model(&[
+            record("llama-q4", 10, 1000),
+            record("llama-q8", 10, 2000),
+            record("llama-q4", 30, 1000),
+        ]);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].model_name, "llama-q4");
+        assert_eq!(summaries[0].predictions, 2);
+        assert_eq!(summaries[0].tokens_per_second, 20.0);
+        assert_eq!(summaries[0].feed_prompt_millis, 200.0);
+        assert_eq!(summaries[1].per_token_millis, 200.0);
+    }
+
+    #[test]
+    fn benchmark_throughputs_spread_over_the_repetitions() {
+        let sample = |repetition, predict_millis| BenchmarkSample {
+            prompt: 0,
+            repetition,
+            stats: InferenceStats {
+                feed_prompt_duration: Duration::from_millis(500),
+                prompt_tokens: 50,
+                predict_duration: Duration::from_millis(predict_millis),
+                predict_tokens: 10,
+            },
+        };
+        // Two prompts per repetition, 20 tokens in 1s then in 0.5s
+        let samples = [sample(0, 500), sample(0, 500), sample(1, 250), sample(1, 250)];
+        let (prompt_eval, generation) = BenchmarkResult::throughputs(&samples, 2);
+        assert_eq!((prompt_eval.mean, prompt_eval.std_dev), (100.0, 0.0));
+        assert_eq!((generation.mean, generation.min, generation.max), (30.0, 20.0, 40.0));
+        assert!((generation.std_dev - 200.0_f64.sqrt()).abs() < 1e-9);
+        assert_eq!(Throughput::of(&[]), Throughput::default());
+    }
+
+    #[test]
+    fn benchmark_sizes_fit_in_the_context() {
+        assert!(BenchmarkConfig::default().check(2048).is_ok());
+        assert!(BenchmarkConfig::default().check(128).is_err());
+        let empty = BenchmarkConfig {
+            repetitions: 0,
+            ..Default::default()
+        };
+        assert!(empty.check(2048).is_err());
+    }
+
+    #[test]
+    fn rope_scaling_is_checked_against_the_context_size() {
+        let params = |context_size, frequency_scale, frequency_base| ModelParameters {
+            context_size,
+            rope_overrides: Some(RoPEOverrides {
+                frequency_scale,
+                frequency_base,
+            }),
+            ..Default::default()
+        };
+        assert!(params(2048, 1.0, 10_000).rope_warnings(2048).is_empty());
+        assert!(params(8192, 0.25, 10_000).rope_warnings(2048).is_empty());
+        assert!(params(4096, 1.0, 20_000).rope_warnings(2048).is_empty());
+        assert!(params(8192, 0.5, 10_000).rope_warnings(2048)[0].contains("about 4096 tokens"));
+        assert!(params(2048, 0.5, 10_000).rope_warnings(2048)[0].contains("not needed"));
+        assert!(params(2048, 0.0, 10_000).rope_warnings(2048)[0].contains("refused"));
+        assert!(params(2048, 1.0, 0).rope_warnings(2048)[0].contains("refused"));
+        // Without overrides the context is checked against the trained one
+        let default = ModelParameters {
+            context_size: 4096,
+            ..Default::default()
+        };
+        assert_eq!(default.rope_warnings(2048).len(), 1);
+        assert!(default.rope_warnings(4096).is_empty());
+    }
+
+    #[test]
+    fn model_config_payload_round_trips() {
+        let payload = PayloadModelConfig {
+            model_config: model_config(),
+        };
+        assert_eq!(argument::<ModelConfig>(&payload, "modelConfig"), model_config());
+    }
+
+    #[test]
+    fn model_params_payload_round_trips() {
+        let payload = PayloadModelParams {
+            params: ModelParams {
+                model_params: model_config().load_params.unwrap(),
+            },
+        };
+        assert_eq!(argument::<ModelParams>(&payload, "params"), payload.params);
+    }
+
+    #[test]
+    fn load_progress_is_tagged_by_kind() {
+        let progress = LoadProgress::TensorLoaded {
+            current_tensor: 3,
+            tensor_count: 291,
+        };
+        let value = serde_json::to_value(&progress).unwrap();
+        assert_eq!(value["kind"], "TensorLoaded");
+        assert_eq!(serde_json::from_value::<LoadProgress>(value).unwrap(), progress);
+    }
+
+    #[test]
+    fn prompt_templates_stored_without_a_layout_get_the_default() {
+        let stored = serde_json::json!({
+            "name": "Alpaca",
+            "system": "",
+            "user": "### Instruction:\n{{PROMPT}}\n\n",
+            "assistant": "### Response:\n{{PROMPT}}\n\n",
+            "stop": ["### Instruction:"],
+        });
+        let template: PromptTemplate = serde_json::from_value(stored).unwrap();
+        assert_eq!(template.layout, DEFAULT_LAYOUT);
+        assert!(!template.builtin);
+        assert!(template.is_valid());
+        let round_trip = serde_json::from_value(serde_json::to_value(&template).unwrap()).unwrap();
+        assert_eq!(template, round_trip);
+    }
+
+    #[test]
+    fn model_configs_stored_before_the_new_fields_are_read() {
+        let stored = serde_json::json!({
+            "name": "llama",
+            "model_architecture": "Llama",
+            "model_path": "/models/llama.bin",
+            "tokenizer_source": "Embedded",
+            "load_params": {
+                "prefer_mmap": true,
+                "context_size": 2048,
+                "lora_adapters": null,
+                "use_gpu": false,
+                "gpu_layers": null,
+            },
+        });
+        let model_config: ModelConfig = serde_json::from_value(stored).unwrap();
+        assert_eq!(model_config.load_params, Some(ModelParameters::default()));
+        assert!(model_config.lora_adapter_sets.is_empty());
+    }
+
+    #[test]
+    fn tokenizer_sources_match_the_fixture() {
+        let fixture: Vec<Value> = serde_json::from_str(TOKENIZER_SOURCES).unwrap();
+        let sources = [
+            TokenizerSource::Embedded,
+            TokenizerSource::HuggingFaceTokenizerFile(PathBuf::from("/models/tokenizer.json")),
+            TokenizerSource::HuggingFaceRemote("hf-internal-testing/llama-tokenizer".to_string()),
+            TokenizerSource::HuggingFaceTokenizerString(r#"{"version":"1.0","model":{"type":"BPE"}}"#.to_string()),
+        ];
+        for (source, value) in sources.iter().zip(&fixture) {
+            assert_eq!(&serde_json::to_value(source).unwrap(), value);
+            assert_eq!(&serde_json::from_value::<TokenizerSource>(value.clone()).unwrap(), source);
+        }
+        assert_eq!(sources.len(), fixture.len());
+    }
+}
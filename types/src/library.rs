@@ -0,0 +1,165 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ModelArchitecture;
+
+/// A directory scanned recursively for model files
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelDirectory {
+    pub path: PathBuf,
+}
+
+/// What a scan changed in the model configs
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ScanReport {
+    /// Names of the model configs created for the new files
+    pub added: Vec<String>,
+    /// Names of the model configs whose file disappeared
+    pub missing: Vec<String>,
+    /// Names of the model configs whose file is back
+    pub restored: Vec<String>,
+}
+
+impl ScanReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.missing.is_empty() && self.restored.is_empty()
+    }
+}
+
+impl std::fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added, {} missing, {} found again",
+            self.added.len(),
+            self.missing.len(),
+            self.restored.len()
+        )
+    }
+}
+
+/// What the header of a model file tells without loading the weights
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelMetadata {
+    /// GGML, GGMF or GGJT
+    pub container: String,
+    pub version: u32,
+    /// Quantization of the weights, e.g. Q4_0
+    pub file_type: String,
+    pub quantization_version: u32,
+    pub vocabulary_size: usize,
+    pub embedding_size: usize,
+    pub layer_count: usize,
+    pub head_count: usize,
+    /// Context length the model was trained with, not every architecture stores it
+    pub context_size: Option<usize>,
+    /// The file does not store the architecture, it is guessed from the hyperparameters
+    pub architecture: ModelArchitecture,
+}
+
+impl std::fmt::Display for ModelMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} v{}, {} (quantization v{}), {:?}: {} layers, {} heads, {} embedding, {} tokens",
+            self.container,
+            self.version,
+            self.file_type,
+            self.quantization_version,
+            self.architecture,
+            self.layer_count,
+            self.head_count,
+            self.embedding_size,
+            self.vocabulary_size,
+        )?;
+        if let Some(context_size) = self.context_size {
+            write!(f, ", trained on {context_size} tokens of context")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the header and the tensors of a LoRA adapter tell about the model it was made for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoraMetadata {
+    pub version: u32,
+    pub rank: u32,
+    pub alpha: u32,
+    pub tensor_count: usize,
+    /// Highest patched layer plus one
+    pub layer_count: usize,
+    /// Sizes of the patched tensors, one of them is the embedding size of the model
+    pub dimensions: Vec<usize>,
+}
+
+impl LoraMetadata {
+    /// The reason why the adapter can not patch the model, if any
+    pub fn incompatibility(&self, model: &ModelMetadata) -> Option<String> {
+        if self.layer_count > model.layer_count {
+            return Some(format!(
+                "it patches {} layers, the model has {}",
+                self.layer_count, model.layer_count
+            ));
+        }
+        if !self.dimensions.contains(&model.embedding_size) {
+            return Some(format!(
+                "it was made for another model size, none of its tensors matches the embedding size {}",
+                model.embedding_size
+            ));
+        }
+        None
+    }
+}
+
+impl std::fmt::Display for LoraMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "rank {}, alpha {}, {} tensors over {} layers",
+            self.rank, self.alpha, self.tensor_count, self.layer_count
+        )
+    }
+}
+
+/// Steps of a model loading, emitted to the frontend as `model_load_progress` events
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind")]
+pub enum LoadProgress {
+    HyperparametersLoaded,
+    /// Memory allocated for the context
+    ContextSize { bytes: usize },
+    LoraApplied { name: String },
+    TensorLoaded {
+        current_tensor: usize,
+        tensor_count: usize,
+    },
+    Loaded {
+        bytes_read: u64,
+        tensor_count: usize,
+    },
+}
+
+impl std::fmt::Display for LoadProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadProgress::HyperparametersLoaded => write!(f, "Hyperparameters loaded"),
+            LoadProgress::ContextSize { bytes } => {
+                write!(f, "Context allocated ({} MB)", bytes / (1024 * 1024))
+            }
+            LoadProgress::LoraApplied { name } => write!(f, "LoRA adapter {name} applied"),
+            LoadProgress::TensorLoaded {
+                current_tensor,
+                tensor_count,
+            } => write!(f, "Tensor {current_tensor} of {tensor_count}"),
+            LoadProgress::Loaded {
+                bytes_read,
+                tensor_count,
+            } => write!(
+                f,
+                "Loaded {tensor_count} tensors ({} MB read)",
+                bytes_read / (1024 * 1024)
+            ),
+        }
+    }
+}